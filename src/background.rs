@@ -0,0 +1,847 @@
+use std::{
+  fmt, fs,
+  path::Path,
+  time::{Duration, Instant},
+};
+
+use egui::{
+  Align2, Color32, ColorImage, Context, Mesh, Painter, Pos2, Rect, TextStyle,
+  TextureHandle, TextureOptions, Ui, Vec2,
+};
+use image::{DynamicImage, RgbaImage, imageops::FilterType};
+use serde::Deserialize;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+use crate::theme::Colour;
+
+/// What a background directory is searched for.
+const IMAGE_EXTENSIONS: [&str; 8] =
+  ["bmp", "gif", "jpeg", "jpg", "png", "qoi", "tiff", "webp"];
+
+/// How many quads a gradient's mesh has along each side.
+const GRADIENT_GRID: u32 = 32;
+
+/// A colour or gradient, a single image, a directory of them, or a list of
+/// images.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum BackgroundSource {
+  Fill(Fill),
+  Path(String),
+  List(Vec<String>),
+}
+
+impl fmt::Display for BackgroundSource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Fill(fill) => write!(f, "{fill:?}"),
+      Self::Path(path) => write!(f, "{path}"),
+      Self::List(paths) => write!(f, "{}", paths.join(", ")),
+    }
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Fill {
+  Colour(Colour),
+  Gradient(Gradient),
+}
+
+impl Default for Fill {
+  fn default() -> Self {
+    Self::Colour(Colour(Color32::BLACK))
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+  #[default]
+  Linear,
+  /// Spreading out from the middle of the screen to its corners.
+  Radial,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Gradient {
+  #[serde(default)]
+  pub shape: Shape,
+  /// Colours spaced evenly from one end of the gradient to the other.
+  pub stops: Vec<Colour>,
+  /// Which way a linear gradient runs, in degrees clockwise from upwards.
+  #[serde(default = "Gradient::default_angle")]
+  pub angle: f32,
+}
+
+impl Fill {
+  pub fn validate(&self) -> Result<(), String> {
+    match self {
+      Self::Gradient(gradient) if gradient.stops.is_empty() => {
+        Err(String::from("a gradient needs at least one stop"))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  fn paint(&self, painter: &Painter, rect: Rect) {
+    match self {
+      Self::Colour(colour) => {
+        painter.rect_filled(rect, 0.0, colour.0);
+      }
+      Self::Gradient(gradient) => {
+        painter.add(gradient.mesh(rect));
+      }
+    }
+  }
+}
+
+impl Gradient {
+  fn default_angle() -> f32 {
+    180.0
+  }
+
+  /// How far along the gradient `point` is, from 0 at the first stop to 1
+  /// at the last.
+  fn position(&self, point: Pos2, rect: Rect) -> f32 {
+    let offset = point - rect.center();
+    match self.shape {
+      // as in CSS, the gradient just reaches the corners at either end
+      Shape::Linear => {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let direction = Vec2::new(sin, -cos);
+        let length = (rect.width() * sin).abs() + (rect.height() * cos).abs();
+        offset.dot(direction) / length + 0.5
+      }
+      Shape::Radial => offset.length() / (rect.size().length() / 2.0),
+    }
+  }
+
+  fn colour_at(&self, position: f32) -> Color32 {
+    let last = self.stops.len() - 1;
+    let scaled = position.clamp(0.0, 1.0) * last as f32;
+    let index = (scaled.floor() as usize).min(last.saturating_sub(1));
+    match &self.stops[index..] {
+      [from, to, ..] => from.0.lerp_to_gamma(to.0, scaled - index as f32),
+      [only] => only.0,
+      [] => Color32::TRANSPARENT,
+    }
+  }
+
+  /// A grid over `rect` coloured at each corner, close enough to the real
+  /// thing for any shape of gradient.
+  fn mesh(&self, rect: Rect) -> Mesh {
+    let mut mesh = Mesh::default();
+    let step = rect.size() / GRADIENT_GRID as f32;
+    for row in 0..=GRADIENT_GRID {
+      for column in 0..=GRADIENT_GRID {
+        let point = rect.min + step * Vec2::new(column as f32, row as f32);
+        mesh.colored_vertex(point, self.colour_at(self.position(point, rect)));
+      }
+    }
+
+    let width = GRADIENT_GRID + 1;
+    for row in 0..GRADIENT_GRID {
+      for column in 0..GRADIENT_GRID {
+        let corner = row * width + column;
+        mesh.add_triangle(corner, corner + 1, corner + width);
+        mesh.add_triangle(corner + 1, corner + width + 1, corner + width);
+      }
+    }
+
+    mesh
+  }
+}
+
+impl BackgroundSource {
+  /// The images to show, with a directory's in name order.
+  pub fn images(&self) -> Result<Vec<String>, String> {
+    match self {
+      Self::Path(path) if Path::new(path).is_dir() => {
+        let mut images = fs::read_dir(path)
+          .map_err(|e| format!("{path}: {e}"))?
+          .filter_map(|entry| Some(entry.ok()?.path()))
+          .filter(|path| {
+            path.extension().is_some_and(|extension| {
+              let extension = extension.to_string_lossy().to_lowercase();
+              IMAGE_EXTENSIONS.contains(&extension.as_str())
+            })
+          })
+          .map(|path| path.to_string_lossy().into_owned())
+          .collect::<Vec<_>>();
+        images.sort();
+        Ok(images)
+      }
+      Self::Path(path) => Ok(vec![path.clone()]),
+      Self::List(paths) => Ok(paths.clone()),
+      Self::Fill(_) => Ok(Vec::new()),
+    }
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+  #[default]
+  Sequential,
+  Random,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlideshowConfig {
+  /// Seconds each image is shown for.
+  pub interval: u64,
+  pub order: Order,
+  /// Seconds spent fading from one image to the next.
+  pub crossfade: f32,
+}
+
+impl Default for SlideshowConfig {
+  fn default() -> Self {
+    Self {
+      interval: 300,
+      order: Order::Sequential,
+      crossfade: 1.0,
+    }
+  }
+}
+
+/// How an image is fitted to the screen.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundMode {
+  /// Fill the screen, cropping whichever sides are too long.
+  #[default]
+  Cover,
+  /// Show all of the image, with letterboxing around it.
+  Contain,
+  Stretch,
+  Tile,
+  /// Show the image at its own size in the middle of the screen.
+  Center,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+  pub mode: BackgroundMode,
+  pub letterbox: Colour,
+  /// How much bigger than their own size tiles are drawn.
+  pub tile_scale: f32,
+}
+
+impl Default for Fit {
+  fn default() -> Self {
+    Self {
+      mode: BackgroundMode::Cover,
+      letterbox: Colour(Color32::BLACK),
+      tile_scale: 1.0,
+    }
+  }
+}
+
+/// Changes made to every image as it's loaded, mostly to keep the text over
+/// it readable.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Effects {
+  /// Radius of the Gaussian blur in pixels, or 0 for none.
+  pub blur: f32,
+  /// How far to darken the image, from 0 for not at all to 1 for black.
+  pub dim: f32,
+  /// Blended over the image as much as its alpha says.
+  pub tint: Option<Colour>,
+  pub grayscale: bool,
+}
+
+impl Effects {
+  pub fn validate(&self) -> Result<(), String> {
+    if !(self.blur.is_finite() && self.blur >= 0.0) {
+      return Err(format!("blur has to be at least 0, not {}", self.blur));
+    }
+    if !(0.0..=1.0).contains(&self.dim) {
+      return Err(format!("dim has to be between 0 and 1, not {}", self.dim));
+    }
+    Ok(())
+  }
+
+  fn apply(&self, mut image: DynamicImage) -> RgbaImage {
+    if self.grayscale {
+      image = image.grayscale();
+    }
+    if self.blur > 0.0 {
+      // a Gaussian fades out by about three sigmas from the middle.
+      // fast_blur approximates one, where blur would take seconds on big
+      // images
+      image = image.fast_blur(self.blur / 3.0);
+    }
+
+    let mut image = image.into_rgba8();
+    let tint = self.tint.map(|Colour(c)| {
+      let [r, g, b, a] = c.to_srgba_unmultiplied();
+      ([r, g, b].map(f32::from), f32::from(a) / 255.0)
+    });
+    let brightness = 1.0 - self.dim;
+    if tint.is_none() && brightness == 1.0 {
+      return image;
+    }
+    for pixel in image.pixels_mut() {
+      for (i, channel) in pixel.0[..3].iter_mut().enumerate() {
+        let mut value = f32::from(*channel);
+        if let Some((colour, amount)) = tint {
+          value += (colour[i] - value) * amount;
+        }
+        *channel = (value * brightness).round() as u8;
+      }
+    }
+    image
+  }
+}
+
+/// Everything about how the background is drawn.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BackgroundConfig {
+  pub source: Option<BackgroundSource>,
+  pub slideshow: SlideshowConfig,
+  pub fit: Fit,
+  pub effects: Effects,
+  /// Drawn under the images, and in their place if there are none or they
+  /// can't be loaded.
+  pub fill: Fill,
+}
+
+struct Slide {
+  texture: TextureHandle,
+  shown_at: Instant,
+}
+
+/// Shows the background images one after another, decoding each one on a
+/// blocking thread while the one before it is still up.
+pub struct Slideshow {
+  config: BackgroundConfig,
+  images: Vec<String>,
+  context: Context,
+  /// The image being loaded, or waiting to be shown once loaded.
+  next: usize,
+  loading: Option<oneshot::Receiver<Result<TextureHandle, String>>>,
+  ready: Option<TextureHandle>,
+  current: Option<Slide>,
+  previous: Option<TextureHandle>,
+  /// Images in a row that couldn't be loaded, to stop trying once every one
+  /// of them has failed.
+  failures: usize,
+  last_failure: Option<String>,
+  /// Whether the first image has been asked for yet.
+  started: bool,
+}
+
+impl Slideshow {
+  pub fn new(config: BackgroundConfig, context: Context) -> Self {
+    let images = config
+      .source
+      .as_ref()
+      .map(|source| {
+        source
+          .images()
+          .inspect_err(|e| println!("couldn't read background: {e}"))
+          .unwrap_or_default()
+      })
+      .unwrap_or_default();
+
+    Self {
+      config,
+      images,
+      context,
+      next: 0,
+      loading: None,
+      ready: None,
+      current: None,
+      previous: None,
+      failures: 0,
+      last_failure: None,
+      started: false,
+    }
+  }
+
+  /// Loads the first image. This waits for the first frame, since until
+  /// then the context only has a placeholder for the biggest texture size.
+  fn start(&mut self) {
+    self.started = true;
+    if self.images.is_empty() {
+      return;
+    }
+    let first = match self.config.slideshow.order {
+      Order::Sequential => 0,
+      Order::Random => rand::random_range(0..self.images.len()),
+    };
+    self.load(first);
+  }
+
+  /// Whether this is already the slideshow for `config`.
+  pub fn shows(&self, config: &BackgroundConfig) -> bool {
+    &self.config == config
+  }
+
+  fn after(&self, index: usize) -> usize {
+    let count = self.images.len();
+    match self.config.slideshow.order {
+      Order::Sequential => (index + 1) % count,
+      // anything but the same image again
+      Order::Random if count > 1 => {
+        let pick = rand::random_range(0..count - 1);
+        if pick >= index { pick + 1 } else { pick }
+      }
+      Order::Random => 0,
+    }
+  }
+
+  fn load(&mut self, index: usize) {
+    let (sender, receiver) = oneshot::channel();
+    let path = self.images[index].clone();
+    let context = self.context.clone();
+    let effects = self.config.effects.clone();
+    let options = match self.config.fit.mode {
+      BackgroundMode::Tile => TextureOptions::LINEAR_REPEAT,
+      _ => TextureOptions::LINEAR,
+    };
+
+    tokio::task::spawn_blocking(move || {
+      let max_side = context.input(|i| i.max_texture_side);
+      let texture = decode(&path, max_side, &effects)
+        .map_err(|e| format!("couldn't load background {path}: {e}"))
+        .inspect_err(|e| println!("{e}"))
+        .map(|image| context.load_texture(path, image, options));
+      let _ = sender.send(texture);
+      context.request_repaint();
+    });
+
+    self.next = index;
+    self.loading = Some(receiver);
+  }
+
+  fn poll(&mut self) {
+    let Some(receiver) = &mut self.loading else {
+      return;
+    };
+
+    match receiver.try_recv() {
+      Err(TryRecvError::Empty) => {}
+      Ok(Ok(texture)) => {
+        self.loading = None;
+        self.ready = Some(texture);
+        self.failures = 0;
+      }
+      Ok(Err(e)) => {
+        self.loading = None;
+        self.last_failure = Some(e);
+        self.failures += 1;
+        if self.failures < self.images.len() {
+          self.load(self.after(self.next));
+        }
+      }
+      Err(TryRecvError::Closed) => {
+        self.loading = None;
+        self.failures += 1;
+        if self.failures < self.images.len() {
+          self.load(self.after(self.next));
+        }
+      }
+    }
+  }
+
+  pub fn paint(&mut self, ui: &mut Ui) {
+    if !self.started {
+      self.start();
+    }
+    self.poll();
+
+    let screen = ui.ctx().screen_rect();
+    self.config.fill.paint(ui.painter(), screen);
+
+    let interval = Duration::from_secs(self.config.slideshow.interval);
+    let due = self
+      .current
+      .as_ref()
+      .is_none_or(|current| current.shown_at.elapsed() >= interval);
+    if due && let Some(texture) = self.ready.take() {
+      self.previous = self.current.take().map(|current| current.texture);
+      self.current = Some(Slide {
+        texture,
+        shown_at: Instant::now(),
+      });
+      if self.images.len() > 1 {
+        self.load(self.after(self.next));
+      }
+    }
+
+    let Some(current) = &self.current else {
+      // a missing image shouldn't leave a blank screen with no explanation
+      if let Some(failure) = &self.last_failure {
+        ui.painter().text(
+          screen.left_bottom() + Vec2::new(10.0, -10.0),
+          Align2::LEFT_BOTTOM,
+          failure,
+          TextStyle::Small.resolve(ui.style()),
+          ui.visuals().error_fg_color,
+        );
+      }
+      return;
+    };
+
+    let shown_for = current.shown_at.elapsed();
+    let crossfade = self.config.slideshow.crossfade;
+    let fade = if crossfade > 0.0 {
+      (shown_for.as_secs_f32() / crossfade).min(1.0)
+    } else {
+      1.0
+    };
+    if fade >= 1.0 {
+      self.previous = None;
+    } else {
+      ui.ctx().request_repaint();
+    }
+
+    let fit = &self.config.fit;
+    if fit.mode == BackgroundMode::Contain {
+      ui.painter().rect_filled(screen, 0.0, fit.letterbox.0);
+    }
+    let paint = |texture: &TextureHandle, tint: Color32| {
+      let size = texture.size_vec2() / ui.ctx().pixels_per_point();
+      let (rect, uv) = placement(fit, size, screen);
+      ui.painter().image(texture.id(), rect, uv, tint);
+    };
+    if let Some(previous) = &self.previous {
+      paint(previous, Color32::WHITE);
+    }
+    paint(
+      &current.texture,
+      Color32::from_white_alpha((fade * 255.0).round() as u8),
+    );
+
+    if self.ready.is_some() {
+      ui.ctx()
+        .request_repaint_after(interval.saturating_sub(shown_for));
+    }
+  }
+}
+
+/// Decodes the image at `path`, shrinking it if needed to fit in a texture,
+/// and applies `effects` to it.
+fn decode(
+  path: &str,
+  max_side: usize,
+  effects: &Effects,
+) -> Result<ColorImage, String> {
+  let mut image = image::open(path).map_err(|e| e.to_string())?;
+  let max_side = max_side as u32;
+  if image.width() > max_side || image.height() > max_side {
+    image = image.resize(max_side, max_side, FilterType::Triangle);
+  }
+
+  let image = effects.apply(image);
+  let size = [image.width() as usize, image.height() as usize];
+  Ok(ColorImage::from_rgba_unmultiplied(size, image.as_raw()))
+}
+
+/// Where on the screen to paint an image of `image` points, and which part
+/// of the image to paint there.
+fn placement(fit: &Fit, image: Vec2, screen: Rect) -> (Rect, Rect) {
+  let whole = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+  // how much wider the image is than the screen, proportionally
+  let aspect = (image.x / image.y) / screen.aspect_ratio();
+
+  match fit.mode {
+    BackgroundMode::Cover => {
+      let size = if aspect < 1.0 {
+        Vec2::new(1.0, aspect)
+      } else {
+        Vec2::new(1.0 / aspect, 1.0)
+      };
+      (
+        screen,
+        Align2::CENTER_CENTER.align_size_within_rect(size, whole),
+      )
+    }
+    BackgroundMode::Contain => {
+      let size = if aspect < 1.0 {
+        Vec2::new(screen.width() * aspect, screen.height())
+      } else {
+        Vec2::new(screen.width(), screen.height() / aspect)
+      };
+      (
+        Align2::CENTER_CENTER.align_size_within_rect(size, screen),
+        whole,
+      )
+    }
+    BackgroundMode::Stretch => (screen, whole),
+    BackgroundMode::Tile => {
+      let tiles = screen.size() / (image * fit.tile_scale);
+      (screen, Rect::from_min_size(Pos2::ZERO, tiles))
+    }
+    BackgroundMode::Center => (
+      Align2::CENTER_CENTER.align_size_within_rect(image, screen),
+      whole,
+    ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn directories_are_searched_for_images() {
+    let dir = std::env::temp_dir()
+      .join(format!("cliffcrown-backgrounds-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for name in ["b.PNG", "a.jpg", "notes.txt", "c.webp"] {
+      fs::write(dir.join(name), "").unwrap();
+    }
+
+    let images = BackgroundSource::Path(dir.to_string_lossy().into_owned())
+      .images()
+      .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    let names = images
+      .iter()
+      .map(|path| Path::new(path).file_name().unwrap().to_str().unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(names, ["a.jpg", "b.PNG", "c.webp"]);
+  }
+
+  #[test]
+  fn parses_every_kind_of_source() {
+    #[derive(Deserialize)]
+    struct Config {
+      background: BackgroundSource,
+    }
+    let parse = |text| toml::de::from_str::<Config>(text).unwrap().background;
+
+    assert_eq!(
+      parse(r#"background = "/a.png""#),
+      BackgroundSource::Path(String::from("/a.png"))
+    );
+    assert_eq!(
+      parse(r#"background = ["/a.png", "/b.png"]"#),
+      BackgroundSource::List(vec![
+        String::from("/a.png"),
+        String::from("/b.png")
+      ])
+    );
+    assert_eq!(
+      parse(r##"background = "#102030""##),
+      BackgroundSource::Fill(Fill::Colour(Colour(Color32::from_rgb(
+        0x10, 0x20, 0x30
+      ))))
+    );
+    assert_eq!(
+      parse(r##"background = { shape = "radial", stops = ["#000", "#fff"] }"##),
+      BackgroundSource::Fill(Fill::Gradient(Gradient {
+        shape: Shape::Radial,
+        stops: vec![Colour(Color32::BLACK), Colour(Color32::WHITE)],
+        angle: 180.0,
+      }))
+    );
+
+    let empty = parse(r#"background = { stops = [] }"#);
+    assert!(
+      matches!(&empty, BackgroundSource::Fill(f) if f.validate().is_err())
+    );
+  }
+
+  #[test]
+  fn gradients_run_corner_to_corner() {
+    let gradient = |shape, angle| Gradient {
+      shape,
+      stops: vec![
+        Colour(Color32::BLACK),
+        Colour(Color32::from_gray(100)),
+        Colour(Color32::WHITE),
+      ],
+      angle,
+    };
+    let screen = rect((0.0, 0.0), (200.0, 100.0));
+    let at = |gradient: &Gradient, point: (f32, f32), expected: f32| {
+      let position = gradient.position(Pos2::new(point.0, point.1), screen);
+      assert!(
+        (position - expected).abs() < 1e-5,
+        "{position} at {point:?}"
+      );
+    };
+
+    // downwards by default
+    let down = gradient(Shape::Linear, 180.0);
+    at(&down, (30.0, 0.0), 0.0);
+    at(&down, (30.0, 100.0), 1.0);
+    at(&gradient(Shape::Linear, 90.0), (50.0, 70.0), 0.25);
+
+    let radial = gradient(Shape::Radial, 180.0);
+    at(&radial, (100.0, 50.0), 0.0);
+    at(&radial, (200.0, 100.0), 1.0);
+
+    assert_eq!(down.colour_at(-1.0), Color32::BLACK);
+    assert_eq!(down.colour_at(0.5), Color32::from_gray(100));
+    assert_eq!(down.colour_at(2.0), Color32::WHITE);
+    let between = down.colour_at(0.75).r();
+    assert!(100 < between && between < 255, "{between}");
+  }
+
+  #[test]
+  fn oversized_images_are_shrunk() {
+    let path = std::env::temp_dir()
+      .join(format!("cliffcrown-oversized-{}.png", std::process::id()));
+    image::RgbaImage::new(8, 4).save(&path).unwrap();
+
+    let decoded = decode(path.to_str().unwrap(), 2, &Effects::default());
+    let _ = fs::remove_file(&path);
+
+    assert_eq!(decoded.unwrap().size, [2, 1]);
+    assert!(
+      decode("/nonexistent/background.png", 2, &Effects::default()).is_err()
+    );
+  }
+
+  #[test]
+  fn effects_change_every_pixel() {
+    let image = || {
+      DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+        2,
+        2,
+        image::Rgba([200, 100, 0, 255]),
+      ))
+    };
+    let pixel = |effects: Effects| effects.apply(image()).get_pixel(1, 1).0;
+
+    assert_eq!(pixel(Effects::default()), [200, 100, 0, 255]);
+    let dimmed = Effects {
+      dim: 0.5,
+      ..Effects::default()
+    };
+    assert_eq!(pixel(dimmed), [100, 50, 0, 255]);
+    let tinted = Effects {
+      tint: Some(Colour(Color32::from_rgba_unmultiplied(0, 0, 255, 51))),
+      ..Effects::default()
+    };
+    assert_eq!(pixel(tinted), [160, 80, 51, 255]);
+    let [r, g, b, _] = pixel(Effects {
+      grayscale: true,
+      blur: 3.0,
+      ..Effects::default()
+    });
+    assert!(r == g && g == b, "{r} {g} {b}");
+
+    let bad = |blur, dim| Effects {
+      blur,
+      dim,
+      ..Effects::default()
+    };
+    assert!(bad(-1.0, 0.0).validate().is_err());
+    assert!(bad(0.0, 1.5).validate().is_err());
+    assert!(bad(10.0, 1.0).validate().is_ok());
+  }
+
+  fn place(mode: BackgroundMode, image: (f32, f32)) -> (Rect, Rect) {
+    let fit = Fit {
+      mode,
+      tile_scale: 2.0,
+      ..Fit::default()
+    };
+    // a 4:3 screen that doesn't start at the origin
+    let screen =
+      Rect::from_min_size(Pos2::new(10.0, 20.0), (400.0, 300.0).into());
+    placement(&fit, image.into(), screen)
+  }
+
+  fn rect(min: (f32, f32), max: (f32, f32)) -> Rect {
+    Rect::from_min_max(min.into(), max.into())
+  }
+
+  const WHOLE: Rect = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+
+  #[test]
+  fn cover_crops_the_long_sides() {
+    // twice as wide as it is high, so a third of the width goes
+    let (area, uv) = place(BackgroundMode::Cover, (800.0, 400.0));
+    assert_eq!(area, rect((10.0, 20.0), (410.0, 320.0)));
+    assert!(uv.min.distance(Pos2::new(1.0 / 6.0, 0.0)) < 1e-6, "{uv:?}");
+    assert!(uv.max.distance(Pos2::new(5.0 / 6.0, 1.0)) < 1e-6, "{uv:?}");
+
+    let (_, uv) = place(BackgroundMode::Cover, (300.0, 900.0));
+    assert!(uv.min.distance(Pos2::new(0.0, 0.375)) < 1e-6, "{uv:?}");
+    assert!(uv.max.distance(Pos2::new(1.0, 0.625)) < 1e-6, "{uv:?}");
+  }
+
+  #[test]
+  fn contain_letterboxes_the_short_sides() {
+    assert_eq!(
+      place(BackgroundMode::Contain, (800.0, 400.0)),
+      (rect((10.0, 70.0), (410.0, 270.0)), WHOLE)
+    );
+    assert_eq!(
+      place(BackgroundMode::Contain, (100.0, 300.0)),
+      (rect((160.0, 20.0), (260.0, 320.0)), WHOLE)
+    );
+  }
+
+  #[test]
+  fn stretch_ignores_the_aspect_ratio() {
+    assert_eq!(
+      place(BackgroundMode::Stretch, (800.0, 400.0)),
+      (rect((10.0, 20.0), (410.0, 320.0)), WHOLE)
+    );
+  }
+
+  #[test]
+  fn tiles_repeat_at_their_scaled_size() {
+    // 50x30 tiles drawn twice as big fit 4 across and 5 down
+    assert_eq!(
+      place(BackgroundMode::Tile, (50.0, 30.0)),
+      (
+        rect((10.0, 20.0), (410.0, 320.0)),
+        rect((0.0, 0.0), (4.0, 5.0))
+      )
+    );
+  }
+
+  #[test]
+  fn center_keeps_the_native_size() {
+    assert_eq!(
+      place(BackgroundMode::Center, (100.0, 50.0)),
+      (rect((160.0, 145.0), (260.0, 195.0)), WHOLE)
+    );
+    // bigger than the screen spills over every edge
+    assert_eq!(
+      place(BackgroundMode::Center, (600.0, 500.0)),
+      (rect((-90.0, -80.0), (510.0, 420.0)), WHOLE)
+    );
+  }
+
+  #[tokio::test]
+  async fn random_order_never_repeats_an_image() {
+    let slideshow = Slideshow::new(
+      BackgroundConfig {
+        source: Some(BackgroundSource::List(vec![
+          String::from("/nonexistent/a.png"),
+          String::from("/nonexistent/b.png"),
+          String::from("/nonexistent/c.png"),
+        ])),
+        slideshow: SlideshowConfig {
+          order: Order::Random,
+          ..SlideshowConfig::default()
+        },
+        ..BackgroundConfig::default()
+      },
+      Context::default(),
+    );
+    // nothing is loaded until the first frame knows the real texture limit
+    assert!(slideshow.loading.is_none());
+
+    for index in (0..3).cycle().take(100) {
+      let next = slideshow.after(index);
+      assert_ne!(next, index);
+      assert!(next < 3);
+    }
+  }
+}
@@ -0,0 +1,111 @@
+use std::{
+  env,
+  fs::{self, File},
+  os::unix::fs::PermissionsExt,
+  path::{Path, PathBuf},
+};
+
+use crate::{CLIArgs, merge_config, read_config_strictly, theme};
+
+/// Reports every problem with the config file given by `args`, then prints
+/// the configuration the greeter would end up with. Returns whether there
+/// were no problems.
+pub fn check_config(args: CLIArgs) -> bool {
+  let path = args.config_path.clone();
+  let stashed_config = match read_config_strictly(&path) {
+    Ok(stashed_config) => stashed_config,
+    Err(e) => {
+      println!("{path}: {e}");
+      return false;
+    }
+  };
+  let config = merge_config(args, stashed_config);
+
+  let mut problems = Vec::new();
+  if let Some(background) = &config.appearance.background.source {
+    match background.images() {
+      Ok(images) if images.is_empty() => {
+        problems.push(format!("couldn't find any images in {background}"))
+      }
+      Ok(images) => {
+        for image in images {
+          if let Err(e) = File::open(&image) {
+            problems.push(format!("couldn't read background {image}: {e}"));
+          }
+        }
+      }
+      Err(e) => problems.push(format!("couldn't read background {e}")),
+    }
+  }
+  for path in config.appearance.theme.fonts.values() {
+    if let Err(e) = theme::load_font(path) {
+      problems.push(format!("couldn't load font {path}: {e}"));
+    }
+  }
+  for session in &config.sessions {
+    match session.command.first() {
+      None => {
+        problems.push(format!("session {:?} has no command", session.name))
+      }
+      // greetd may start sessions with a different PATH, but it's a start
+      Some(program) if find_program(program).is_none() => {
+        problems.push(format!(
+          "couldn't find {program} in PATH for session {:?}",
+          session.name
+        ))
+      }
+      Some(_) => {}
+    }
+  }
+
+  println!("{config:#?}");
+  for problem in &problems {
+    println!("{problem}");
+  }
+
+  problems.is_empty()
+}
+
+fn find_program(program: &str) -> Option<PathBuf> {
+  let is_executable = |path: &Path| {
+    fs::metadata(path)
+      .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+  };
+
+  if program.contains('/') {
+    let path = PathBuf::from(program);
+    return is_executable(&path).then_some(path);
+  }
+
+  env::split_paths(&env::var_os("PATH")?)
+    .map(|dir| dir.join(program))
+    .find(|path| is_executable(path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::StashedConfig;
+
+  #[test]
+  fn finds_programs() {
+    assert!(find_program("sh").is_some());
+    assert!(find_program("/bin/sh").is_some());
+    assert!(find_program("cliffcrown-no-such-program").is_none());
+    // not executable
+    assert!(find_program("/etc/passwd").is_none());
+  }
+
+  #[test]
+  fn unknown_keys_are_pinpointed() {
+    let e = toml::de::from_str::<StashedConfig>(
+      "prompt_timeout = 30\n\n[users]\nlist = true\nmax_iud = 2000\n",
+    )
+    .err()
+    .unwrap()
+    .to_string();
+
+    assert!(e.contains("line 5, column 1"), "{e}");
+    assert!(e.contains("unknown field `max_iud`"), "{e}");
+  }
+}
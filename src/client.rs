@@ -1,11 +1,23 @@
-use std::{os::unix::net::UnixStream, sync::Arc};
+use std::time::{Duration, Instant, SystemTime};
 
-use egui::Context;
+use demo::DemoManager;
 use either::Either::{self, Left, Right};
-use greetd_ipc::codec::SyncCodec;
-use tokio::sync::oneshot;
+use greetd_ipc::codec::TokioCodec;
+use script::{Conversation, ScriptManager};
+use throttle::{Throttle, ThrottleConfig};
+use tokio::{net::UnixStream, sync::oneshot};
 
-const GREETD_SOCK_ENV: &'static str = "GREETD_SOCK";
+pub mod demo;
+#[cfg(test)]
+mod fake_greetd;
+pub mod script;
+pub mod throttle;
+
+const GREETD_SOCK_ENV: &str = "GREETD_SOCK";
+pub const DEFAULT_RECONNECT_ATTEMPTS: u32 = 5;
+/// The wait before the first reconnection, doubling with each one after.
+const RECONNECT_DELAY: Duration = Duration::from_millis(250);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(8);
 
 #[derive(Debug)]
 pub enum ClientError {
@@ -15,6 +27,10 @@ pub enum ClientError {
   FailedSocketRead(greetd_ipc::codec::Error),
   GenericError(String),
   AuthError(String),
+  Disconnected {
+    attempts: u32,
+    error: Box<ClientError>,
+  },
 }
 
 impl std::fmt::Display for ClientError {
@@ -39,12 +55,69 @@ impl std::fmt::Display for ClientError {
       Self::AuthError(e) => {
         write!(f, "authentication error: {}", e)
       }
+      Self::Disconnected { attempts: 0, error } => {
+        write!(f, "lost the connection to greetd: {}", error)
+      }
+      Self::Disconnected { attempts, error } => write!(
+        f,
+        "lost the connection to greetd and gave up after {} attempts to \
+         reconnect: {}",
+        attempts, error
+      ),
     }
   }
 }
 
 impl std::error::Error for ClientError {}
 
+impl ClientError {
+  fn from_greetd(
+    error_type: greetd_ipc::ErrorType,
+    description: String,
+  ) -> Self {
+    match error_type {
+      greetd_ipc::ErrorType::Error => ClientError::GenericError(description),
+      greetd_ipc::ErrorType::AuthError => ClientError::AuthError(description),
+    }
+  }
+
+  /// Whether this came from the socket itself going away, rather than
+  /// something greetd said, so reconnecting might fix it.
+  fn is_disconnect(&self) -> bool {
+    use greetd_ipc::codec::Error;
+
+    match self {
+      Self::FailedSocketConnection(_) => true,
+      Self::FailedSocketRead(e) | Self::FailedSocketWrite(e) => {
+        matches!(e, Error::Io(_) | Error::Eof)
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Reads greetd's reply to a request which should only ever be acknowledged,
+/// like `StartSession` or `CancelSession`.
+async fn read_acknowledgement(
+  stream: &mut UnixStream,
+) -> Result<(), ClientError> {
+  match greetd_ipc::Response::read_from(stream)
+    .await
+    .map_err(ClientError::FailedSocketRead)?
+  {
+    greetd_ipc::Response::Success => Ok(()),
+    greetd_ipc::Response::Error {
+      error_type,
+      description,
+    } => Err(ClientError::from_greetd(error_type, description)),
+    greetd_ipc::Response::AuthMessage { auth_message, .. } => {
+      Err(ClientError::GenericError(format!(
+        "unexpected auth message: {auth_message}"
+      )))
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub enum AuthPrompt {
   Input { prompt: String, secret: bool },
@@ -74,22 +147,23 @@ pub struct SuccessfulClient {
 }
 
 impl Client {
-  pub fn new() -> Result<Self, ClientError> {
+  pub async fn new() -> Result<Self, ClientError> {
     let sock =
       std::env::var(GREETD_SOCK_ENV).map_err(|_| ClientError::MissingEnvVar)?;
 
     let stream = UnixStream::connect(sock)
-      .map_err(|e| ClientError::FailedSocketConnection(e))?;
+      .await
+      .map_err(ClientError::FailedSocketConnection)?;
 
     Ok(Self { stream })
   }
 
-  pub fn create_session(
+  pub async fn create_session(
     mut self,
     username: String,
   ) -> Result<ActiveClient, (ClientError, Self)> {
     let request = greetd_ipc::Request::CreateSession { username };
-    if let Err(e) = request.write_to(&mut self.stream) {
+    if let Err(e) = request.write_to(&mut self.stream).await {
       return Err((ClientError::FailedSocketWrite(e), self));
     }
 
@@ -100,11 +174,23 @@ impl Client {
 }
 
 impl ActiveClient {
-  pub fn next(
+  /// Waits until greetd has started to reply. Unlike [`ActiveClient::next`]
+  /// this is cancel safe, so it can be raced against other events in
+  /// `tokio::select!` and followed by either `next` or `cancel`.
+  pub async fn ready(&self) -> Result<(), ClientError> {
+    self
+      .stream
+      .readable()
+      .await
+      .map_err(|e| ClientError::FailedSocketRead(e.into()))
+  }
+
+  pub async fn next(
     mut self,
   ) -> Result<Either<PromptingClient, SuccessfulClient>, (ClientError, Client)>
   {
-    let response = match greetd_ipc::Response::read_from(&mut self.stream) {
+    let response = match greetd_ipc::Response::read_from(&mut self.stream).await
+    {
       Ok(r) => r,
       Err(e) => {
         return Err((
@@ -124,14 +210,7 @@ impl ActiveClient {
         error_type,
         description,
       } => Err((
-        match error_type {
-          greetd_ipc::ErrorType::Error => {
-            ClientError::GenericError(description)
-          }
-          greetd_ipc::ErrorType::AuthError => {
-            ClientError::AuthError(description)
-          }
-        },
+        ClientError::from_greetd(error_type, description),
         Client {
           stream: self.stream,
         },
@@ -161,12 +240,18 @@ impl ActiveClient {
     }
   }
 
-  pub fn cancel(mut self) -> (Client, Option<ClientError>) {
+  pub async fn cancel(mut self) -> (Client, Option<ClientError>) {
     let request = greetd_ipc::Request::CancelSession;
-    let error = request
-      .write_to(&mut self.stream)
-      .map_err(|e| ClientError::FailedSocketWrite(e))
-      .err();
+    let error = match request.write_to(&mut self.stream).await {
+      // greetd answers requests in order, so the reply to whatever this
+      // client is still waiting on comes before the cancellation is
+      // acknowledged
+      Ok(()) => match greetd_ipc::Response::read_from(&mut self.stream).await {
+        Ok(_) => read_acknowledgement(&mut self.stream).await.err(),
+        Err(e) => Some(ClientError::FailedSocketRead(e)),
+      },
+      Err(e) => Some(ClientError::FailedSocketWrite(e)),
+    };
     (
       Client {
         stream: self.stream,
@@ -177,13 +262,13 @@ impl ActiveClient {
 }
 
 impl PromptingClient {
-  pub fn next(
+  pub async fn next(
     mut self,
     answer: Option<String>,
   ) -> Result<ActiveClient, (ClientError, Self)> {
     let request =
       greetd_ipc::Request::PostAuthMessageResponse { response: answer };
-    if let Err(e) = request.write_to(&mut self.stream) {
+    if let Err(e) = request.write_to(&mut self.stream).await {
       return Err((ClientError::FailedSocketWrite(e), self));
     }
 
@@ -192,12 +277,12 @@ impl PromptingClient {
     })
   }
 
-  pub fn cancel(mut self) -> (Client, Option<ClientError>) {
+  pub async fn cancel(mut self) -> (Client, Option<ClientError>) {
     let request = greetd_ipc::Request::CancelSession;
-    let error = request
-      .write_to(&mut self.stream)
-      .map_err(|e| ClientError::FailedSocketWrite(e))
-      .err();
+    let error = match request.write_to(&mut self.stream).await {
+      Ok(()) => read_acknowledgement(&mut self.stream).await.err(),
+      Err(e) => Some(ClientError::FailedSocketWrite(e)),
+    };
     (
       Client {
         stream: self.stream,
@@ -208,7 +293,21 @@ impl PromptingClient {
 }
 
 impl SuccessfulClient {
-  pub fn finish(
+  pub async fn cancel(mut self) -> (Client, Option<ClientError>) {
+    let request = greetd_ipc::Request::CancelSession;
+    let error = match request.write_to(&mut self.stream).await {
+      Ok(()) => read_acknowledgement(&mut self.stream).await.err(),
+      Err(e) => Some(ClientError::FailedSocketWrite(e)),
+    };
+    (
+      Client {
+        stream: self.stream,
+      },
+      error,
+    )
+  }
+
+  pub async fn finish(
     mut self,
     command: Vec<String>,
     environment: Vec<String>,
@@ -217,14 +316,30 @@ impl SuccessfulClient {
       cmd: command,
       env: environment,
     };
-    request
-      .write_to(&mut self.stream)
-      .map_err(|e| (ClientError::FailedSocketWrite(e), self))
+    if let Err(e) = request.write_to(&mut self.stream).await {
+      return Err((ClientError::FailedSocketWrite(e), self));
+    }
+
+    read_acknowledgement(&mut self.stream)
+      .await
+      .map_err(|e| (e, self))
   }
 }
 
-pub type UsernamePacket = (String, oneshot::Sender<StatePacket>);
+pub type UsernamePacket = (
+  String,
+  oneshot::Sender<StatePacket>,
+  oneshot::Receiver<CancelPacket>,
+);
 pub type PromptResponsePacket = (Option<String>, oneshot::Sender<StatePacket>);
+/// The command to launch and the `KEY=VALUE` environment to launch it with,
+/// along with where to report it if greetd refuses to start it.
+pub type SessionPacket =
+  (Vec<String>, Vec<String>, oneshot::Sender<StatePacket>);
+/// Abandons the current attempt, carrying the channel the username for the
+/// next one will arrive on.
+#[derive(Debug)]
+pub struct CancelPacket(pub oneshot::Receiver<UsernamePacket>);
 
 #[derive(Debug)]
 pub enum StatePacket {
@@ -233,60 +348,817 @@ pub enum StatePacket {
     response_sender: oneshot::Sender<PromptResponsePacket>,
   },
   Success {
-    command_sender: oneshot::Sender<Vec<String>>,
+    session_sender: oneshot::Sender<SessionPacket>,
+  },
+  Failure {
+    reason: String,
+    retry_sender: oneshot::Sender<UsernamePacket>,
+  },
+  /// Too many attempts have failed for this user, so the next one waits
+  /// until `until` and then reports back on `state_receiver`.
+  Throttled {
+    until: Instant,
+    locked_out: bool,
+    state_receiver: oneshot::Receiver<StatePacket>,
+  },
+  /// The connection to greetd dropped and is being remade, after which the
+  /// attempt starts over and reports back on `state_receiver`.
+  Reconnecting {
+    state_receiver: oneshot::Receiver<StatePacket>,
+  },
+  /// Something other than authentication went wrong. Retrying starts over
+  /// on a new connection to greetd.
+  Error {
+    error: ClientError,
+    retry_sender: oneshot::Sender<UsernamePacket>,
+  },
+}
+
+/// What the UI logs in against.
+#[derive(Clone, Debug)]
+pub enum Backend {
+  Greetd {
+    reconnect_attempts: u32,
+    throttle: ThrottleConfig,
+  },
+  Demo {
+    password: String,
+  },
+  Script {
+    conversation: Conversation,
   },
 }
 
+impl Backend {
+  /// Spawns a manager for this backend, returning where to send the first
+  /// username.
+  pub fn start(&self) -> oneshot::Sender<UsernamePacket> {
+    match self {
+      Self::Greetd {
+        reconnect_attempts,
+        throttle,
+      } => {
+        let (starter, manager) = ClientManager::new(
+          *reconnect_attempts,
+          Throttle::load(throttle.clone()),
+        );
+        tokio::spawn(manager.run());
+        starter
+      }
+      Self::Demo { password } => {
+        let (starter, manager) = DemoManager::new(password.clone());
+        tokio::spawn(manager.run());
+        starter
+      }
+      Self::Script { conversation } => {
+        let (starter, manager) = ScriptManager::new(conversation.clone());
+        tokio::spawn(async move {
+          if let Err(e) = manager.run().await {
+            println!("the greeter strayed from the script:\n{e}");
+          }
+        });
+        starter
+      }
+    }
+  }
+}
+
 pub struct ClientManager {
   receiver: oneshot::Receiver<UsernamePacket>,
-  client: Client,
+  reconnect_attempts: u32,
+  throttle: Throttle,
+}
+
+fn recovered((client, error): (Client, Option<ClientError>)) -> Option<Client> {
+  match error {
+    Some(e) => {
+      println!("couldn't cancel session: {e}");
+      None
+    }
+    None => Some(client),
+  }
+}
+
+/// Shows `prompt` and waits for the answer, along with where to send the
+/// next packet. Only the pretend backends get to ask questions this plainly.
+async fn ask(
+  responder: oneshot::Sender<StatePacket>,
+  prompt: AuthPrompt,
+) -> Option<(Option<String>, oneshot::Sender<StatePacket>)> {
+  let (response_sender, response_receiver) = oneshot::channel();
+  responder
+    .send(StatePacket::Prompt {
+      prompt,
+      response_sender,
+    })
+    .ok()?;
+  response_receiver.await.ok()
+}
+
+/// Reports `reason` as a failed login and waits for the next username.
+async fn fail(
+  responder: oneshot::Sender<StatePacket>,
+  reason: String,
+) -> Option<UsernamePacket> {
+  let (retry_sender, retry_receiver) = oneshot::channel();
+  responder
+    .send(StatePacket::Failure {
+      reason,
+      retry_sender,
+    })
+    .ok()?;
+  retry_receiver.await.ok()
+}
+
+/// Sends `packet` down the current responder, which is used up by doing so.
+fn respond(
+  responder: &mut Option<oneshot::Sender<StatePacket>>,
+  packet: StatePacket,
+) {
+  if let Some(responder) = responder.take() {
+    let _ = responder.send(packet);
+  }
 }
 
 impl ClientManager {
-  pub fn new() -> Result<(oneshot::Sender<UsernamePacket>, Self), ClientError> {
+  /// The connection to greetd is only made once the first username arrives,
+  /// so that any problem with it can be shown to the user.
+  pub fn new(
+    reconnect_attempts: u32,
+    throttle: Throttle,
+  ) -> (oneshot::Sender<UsernamePacket>, Self) {
     let (sender, receiver) = oneshot::channel();
-    Ok((
+    (
       sender,
       ClientManager {
         receiver,
-        client: Client::new()?,
+        reconnect_attempts,
+        throttle,
       },
-    ))
+    )
   }
 
-  pub async fn run(self) -> Result<(), ClientError> {
-    let ClientManager {
-      receiver: username_receiver,
-      client,
-    } = self;
+  pub async fn run(mut self) {
+    // whenever one of the UI's channels closes without a cancellation coming
+    // through, the UI has gone away and there is nobody left to log in
+    let Ok(mut packet) = self.receiver.await else {
+      return;
+    };
+    let mut client = None;
 
-    let (username, mut responder) = username_receiver.await.unwrap();
-    let mut active_client =
-      client.create_session(username).map_err(|(e, _)| e)?;
+    loop {
+      let (username, responder, mut cancel_receiver) = packet;
+      let mut responder = Some(responder);
+      let mut reconnections = 0;
+
+      if let Some(wait) = self.throttle.wait(&username, SystemTime::now()) {
+        let (state_sender, state_receiver) = oneshot::channel();
+        respond(
+          &mut responder,
+          StatePacket::Throttled {
+            until: Instant::now() + wait.remaining,
+            locked_out: wait.locked_out,
+            state_receiver,
+          },
+        );
+        responder = Some(state_sender);
+
+        tokio::select! {
+          biased;
+          Ok(CancelPacket(restart)) = &mut cancel_receiver => {
+            let Ok(next) = restart.await else { return };
+            packet = next;
+            continue;
+          }
+          _ = tokio::time::sleep(wait.remaining) => {}
+        }
+      }
+
+      let result = loop {
+        let error = match Self::attempt(
+          &mut client,
+          &mut self.throttle,
+          username.clone(),
+          &mut responder,
+          &mut cancel_receiver,
+        )
+        .await
+        {
+          Err(error) if error.is_disconnect() => error,
+          result => break result,
+        };
+
+        client = None;
+        if reconnections == self.reconnect_attempts {
+          break Err(ClientError::Disconnected {
+            attempts: reconnections,
+            error: Box::new(error),
+          });
+        }
+        println!("lost the connection to greetd, reconnecting: {error}");
+
+        let (state_sender, state_receiver) = oneshot::channel();
+        respond(&mut responder, StatePacket::Reconnecting { state_receiver });
+        responder = Some(state_sender);
+
+        let delay = RECONNECT_DELAY * 2_u32.pow(reconnections.min(8));
+        reconnections += 1;
+        tokio::select! {
+          biased;
+          Ok(CancelPacket(restart)) = &mut cancel_receiver => {
+            break Ok(restart.await.ok());
+          }
+          // the attempt makes a new connection when it starts over
+          _ = tokio::time::sleep(delay.min(MAX_RECONNECT_DELAY)) => {}
+        }
+      };
+
+      let error = match result {
+        Ok(Some(next)) => {
+          packet = next;
+          continue;
+        }
+        Ok(None) => return,
+        Err(error) => error,
+      };
+
+      // the connection could have been left in any state, so the next
+      // attempt starts over on a fresh one
+      client = None;
+      let (retry_sender, retry_receiver) = oneshot::channel();
+      respond(
+        &mut responder,
+        StatePacket::Error {
+          error,
+          retry_sender,
+        },
+      );
+
+      packet = tokio::select! {
+        biased;
+        Ok(CancelPacket(restart)) = &mut cancel_receiver => {
+          let Ok(next) = restart.await else { return };
+          next
+        }
+        Ok(next) = retry_receiver => next,
+        else => return,
+      };
+    }
+  }
+
+  /// Takes one username through to a started session, a cancellation or a
+  /// failed login, returning the packet for the next attempt if there is
+  /// one. Whenever this fails `responder` is left in place to report it.
+  async fn attempt(
+    client: &mut Option<Client>,
+    throttle: &mut Throttle,
+    username: String,
+    responder: &mut Option<oneshot::Sender<StatePacket>>,
+    cancel_receiver: &mut oneshot::Receiver<CancelPacket>,
+  ) -> Result<Option<UsernamePacket>, ClientError> {
+    let connection = match client.take() {
+      Some(connection) => connection,
+      None => Client::new().await?,
+    };
+    let mut active_client = connection
+      .create_session(username.clone())
+      .await
+      .map_err(|(e, _)| e)?;
 
     loop {
-      match active_client.next().map_err(|(e, _)| e)? {
-        Left(prompting_client) => {
+      tokio::select! {
+        biased;
+        Ok(CancelPacket(restart)) = &mut *cancel_receiver => {
+          *client = recovered(active_client.cancel().await);
+          return Ok(restart.await.ok());
+        }
+        ready = active_client.ready() => ready?,
+      }
+
+      match active_client.next().await {
+        Ok(Left(prompting_client)) => {
           let (prompt_sender, prompt_receiver) = oneshot::channel();
-          responder.send(StatePacket::Prompt {
-            prompt: prompting_client.prompt.clone(),
-            response_sender: prompt_sender,
-          });
-          let (prompt_response, new_responder) = prompt_receiver.await.unwrap();
-          responder = new_responder;
-          active_client =
-            prompting_client.next(prompt_response).map_err(|(e, _)| e)?;
+          respond(
+            responder,
+            StatePacket::Prompt {
+              prompt: prompting_client.prompt.clone(),
+              response_sender: prompt_sender,
+            },
+          );
+
+          tokio::select! {
+            biased;
+            Ok(CancelPacket(restart)) = &mut *cancel_receiver => {
+              *client = recovered(prompting_client.cancel().await);
+              return Ok(restart.await.ok());
+            }
+            Ok((prompt_response, new_responder)) = prompt_receiver => {
+              *responder = Some(new_responder);
+              active_client = prompting_client
+                .next(prompt_response)
+                .await
+                .map_err(|(e, _)| e)?;
+            }
+            else => return Ok(None),
+          }
         }
-        Right(successful_client) => {
-          let (command_sender, command_receiver) = oneshot::channel();
-          responder.send(StatePacket::Success { command_sender });
-          let command = command_receiver.await.unwrap();
-          successful_client
-            .finish(command, vec![])
-            .map_err(|(e, _)| e)?;
-          return Ok(());
+        Ok(Right(successful_client)) => {
+          throttle.record_success(&username);
+          let (session_sender, session_receiver) = oneshot::channel();
+          respond(responder, StatePacket::Success { session_sender });
+
+          tokio::select! {
+            biased;
+            Ok(CancelPacket(restart)) = &mut *cancel_receiver => {
+              *client = recovered(successful_client.cancel().await);
+              return Ok(restart.await.ok());
+            }
+            Ok((command, environment, new_responder)) = session_receiver => {
+              *responder = Some(new_responder);
+              successful_client
+                .finish(command, environment)
+                .await
+                .map_err(|(e, _)| e)?;
+              return Ok(None);
+            }
+            else => return Ok(None),
+          }
+        }
+        Err((ClientError::AuthError(reason), returned_client)) => {
+          // greetd tears the session down itself after an auth error, so
+          // the same connection can go straight into a new attempt
+          *client = Some(returned_client);
+          throttle.record_failure(&username, SystemTime::now());
+          let (retry_sender, retry_receiver) = oneshot::channel();
+          respond(
+            responder,
+            StatePacket::Failure {
+              reason,
+              retry_sender,
+            },
+          );
+
+          // leaving the failure screen by cancelling rather than
+          // confirming still starts a new attempt, just on a different
+          // channel
+          tokio::select! {
+            biased;
+            Ok(CancelPacket(restart)) = &mut *cancel_receiver => {
+              return Ok(restart.await.ok());
+            }
+            Ok(next) = retry_receiver => return Ok(Some(next)),
+            else => return Ok(None),
+          }
         }
+        Err((e, _)) => return Err(e),
       }
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+  use fake_greetd::Script;
+  use throttle::ThrottleConfig;
+
+  fn unthrottled() -> Throttle {
+    Throttle::load(ThrottleConfig {
+      delay: 0,
+      lockout_after: 0,
+      ..Default::default()
+    })
+  }
+
+  async fn expect_prompt(active_client: ActiveClient) -> PromptingClient {
+    match active_client.next().await {
+      Ok(Left(prompting_client)) => prompting_client,
+      Ok(Right(_)) => panic!("expected a prompt, got success"),
+      Err((e, _)) => panic!("expected a prompt, got {e}"),
+    }
+  }
+
+  #[tokio::test]
+  async fn password_login() {
+    let greetd = Script::new()
+      .expect_session("alice")
+      .secret("Password:")
+      .expect_answer(Some("hunter2"))
+      .success()
+      .expect_start(&["sway"], &["XDG_SESSION_TYPE=wayland"])
+      .success()
+      .serve();
+
+    let client = Client::new().await.unwrap();
+    let active_client = client.create_session("alice".into()).await.unwrap();
+    let prompting_client = expect_prompt(active_client).await;
+    assert!(matches!(
+      &prompting_client.prompt,
+      AuthPrompt::Input { prompt, secret: true } if prompt == "Password:"
+    ));
+
+    let active_client =
+      prompting_client.next(Some("hunter2".into())).await.unwrap();
+    let Ok(Right(successful_client)) = active_client.next().await else {
+      panic!("expected success");
+    };
+    successful_client
+      .finish(vec!["sway".into()], vec!["XDG_SESSION_TYPE=wayland".into()])
+      .await
+      .unwrap();
+
+    greetd.finish();
+  }
+
+  #[tokio::test]
+  async fn info_and_error_messages() {
+    let greetd = Script::new()
+      .expect_session("bob")
+      .visible("Token:")
+      .expect_answer(Some("123456"))
+      .info("Touch your key")
+      .expect_answer(None)
+      .error("Key not recognised")
+      .expect_answer(None)
+      .auth_error("pam_authenticate: AUTH_ERR")
+      .serve();
+
+    let client = Client::new().await.unwrap();
+    let active_client = client.create_session("bob".into()).await.unwrap();
+    let prompting_client = expect_prompt(active_client).await;
+    assert!(matches!(
+      &prompting_client.prompt,
+      AuthPrompt::Input { prompt, secret: false } if prompt == "Token:"
+    ));
+
+    let active_client =
+      prompting_client.next(Some("123456".into())).await.unwrap();
+    let prompting_client = expect_prompt(active_client).await;
+    assert!(matches!(
+      &prompting_client.prompt,
+      AuthPrompt::Info { note } if note == "Touch your key"
+    ));
+
+    let active_client = prompting_client.next(None).await.unwrap();
+    let prompting_client = expect_prompt(active_client).await;
+    assert!(matches!(
+      &prompting_client.prompt,
+      AuthPrompt::Error { note } if note == "Key not recognised"
+    ));
+
+    let active_client = prompting_client.next(None).await.unwrap();
+    let Err((ClientError::AuthError(reason), _)) = active_client.next().await
+    else {
+      panic!("expected an auth error");
+    };
+    assert_eq!(reason, "pam_authenticate: AUTH_ERR");
+
+    greetd.finish();
+  }
+
+  #[tokio::test]
+  async fn cancel_returns_a_reusable_client() {
+    let greetd = Script::new()
+      .expect_session("alice")
+      .secret("Password:")
+      .expect_cancel()
+      .success()
+      .expect_session("bob")
+      .generic_error("no such user")
+      .serve();
+
+    let client = Client::new().await.unwrap();
+    let active_client = client.create_session("alice".into()).await.unwrap();
+    let (client, error) = expect_prompt(active_client).await.cancel().await;
+    assert!(error.is_none());
+
+    let active_client = client.create_session("bob".into()).await.unwrap();
+    let Err((ClientError::GenericError(reason), _)) =
+      active_client.next().await
+    else {
+      panic!("expected a generic error");
+    };
+    assert_eq!(reason, "no such user");
+
+    greetd.finish();
+  }
+
+  #[tokio::test]
+  async fn cancel_while_waiting_on_greetd() {
+    let greetd = Script::new()
+      .expect_session("alice")
+      .expect_cancel()
+      .info("Place your finger on the reader")
+      .success()
+      .serve();
+
+    let client = Client::new().await.unwrap();
+    let active_client = client.create_session("alice".into()).await.unwrap();
+
+    tokio::select! {
+      _ = active_client.ready() => panic!("greetd should not have replied"),
+      _ = tokio::time::sleep(Duration::from_millis(50)) => {}
+    }
+
+    let (_, error) = active_client.cancel().await;
+    assert!(error.is_none());
+
+    greetd.finish();
+  }
+
+  #[tokio::test]
+  async fn missing_socket_variable() {
+    let greetd = Script::new().serve();
+    // SAFETY: the fake greetd holds the environment lock until it is dropped
+    unsafe { std::env::remove_var(GREETD_SOCK_ENV) };
+
+    assert!(matches!(
+      Client::new().await,
+      Err(ClientError::MissingEnvVar)
+    ));
+
+    drop(greetd);
+  }
+
+  #[tokio::test]
+  async fn manager_retries_after_auth_error() {
+    let greetd = Script::new()
+      .expect_session("alice")
+      .secret("Password:")
+      .expect_answer(Some("wrong"))
+      .auth_error("pam_authenticate: AUTH_ERR")
+      .expect_session("alice")
+      .secret("Password:")
+      .expect_answer(Some("hunter2"))
+      .success()
+      .expect_start(&["sway"], &["XDG_CURRENT_DESKTOP=sway"])
+      .success()
+      .serve();
+
+    let (starter, client_manager) =
+      ClientManager::new(DEFAULT_RECONNECT_ATTEMPTS, unthrottled());
+    let manager = tokio::spawn(client_manager.run());
+
+    let mut start_client = starter;
+    for password in ["wrong", "hunter2"] {
+      let (state_sender, state_receiver) = oneshot::channel();
+      let (_cancel_sender, cancel_receiver) = oneshot::channel();
+      start_client
+        .send(("alice".into(), state_sender, cancel_receiver))
+        .unwrap();
+
+      let StatePacket::Prompt {
+        response_sender, ..
+      } = state_receiver.await.unwrap()
+      else {
+        panic!("expected a prompt");
+      };
+      let (state_sender, state_receiver) = oneshot::channel();
+      response_sender
+        .send((Some(password.into()), state_sender))
+        .unwrap();
+
+      match state_receiver.await.unwrap() {
+        StatePacket::Failure {
+          reason,
+          retry_sender,
+        } => {
+          assert_eq!(password, "wrong");
+          assert_eq!(reason, "pam_authenticate: AUTH_ERR");
+          start_client = retry_sender;
+        }
+        StatePacket::Success { session_sender } => {
+          assert_eq!(password, "hunter2");
+          let (state_sender, state_receiver) = oneshot::channel();
+          session_sender
+            .send((
+              vec!["sway".into()],
+              vec!["XDG_CURRENT_DESKTOP=sway".into()],
+              state_sender,
+            ))
+            .unwrap();
+          // a started session is never reported back
+          assert!(state_receiver.await.is_err());
+          break;
+        }
+        StatePacket::Prompt { .. } => panic!("unexpected prompt"),
+        StatePacket::Reconnecting { .. } => panic!("unexpected reconnection"),
+        StatePacket::Throttled { .. } => panic!("unexpected throttling"),
+        StatePacket::Error { error, .. } => panic!("unexpected error {error}"),
+      }
+    }
+
+    manager.await.unwrap();
+    greetd.finish();
+  }
+
+  #[tokio::test]
+  async fn manager_cancels_at_a_prompt() {
+    let greetd = Script::new()
+      .expect_session("alice")
+      .secret("Password:")
+      .expect_cancel()
+      .success()
+      .expect_session("bob")
+      .success()
+      .expect_start(&["sway"], &[])
+      .success()
+      .serve();
+
+    let (starter, client_manager) =
+      ClientManager::new(DEFAULT_RECONNECT_ATTEMPTS, unthrottled());
+    let manager = tokio::spawn(client_manager.run());
+
+    let (state_sender, state_receiver) = oneshot::channel();
+    let (cancel_sender, cancel_receiver) = oneshot::channel();
+    starter
+      .send(("alice".into(), state_sender, cancel_receiver))
+      .unwrap();
+    let StatePacket::Prompt {
+      response_sender, ..
+    } = state_receiver.await.unwrap()
+    else {
+      panic!("expected a prompt");
+    };
+
+    // the UI drops whatever it was holding before it cancels
+    drop(response_sender);
+    let (restart_sender, restart_receiver) = oneshot::channel();
+    cancel_sender.send(CancelPacket(restart_receiver)).unwrap();
+
+    let (state_sender, state_receiver) = oneshot::channel();
+    let (_cancel_sender, cancel_receiver) = oneshot::channel();
+    restart_sender
+      .send(("bob".into(), state_sender, cancel_receiver))
+      .unwrap();
+    let StatePacket::Success { session_sender } = state_receiver.await.unwrap()
+    else {
+      panic!("expected success");
+    };
+    let (state_sender, _state_receiver) = oneshot::channel();
+    session_sender
+      .send((vec!["sway".into()], vec![], state_sender))
+      .unwrap();
+
+    manager.await.unwrap();
+    greetd.finish();
+  }
+
+  #[tokio::test]
+  async fn manager_reconnects_after_an_error() {
+    let greetd = Script::new()
+      .expect_session("alice")
+      .generic_error("session already active")
+      .disconnect()
+      .expect_session("alice")
+      .success()
+      .expect_start(&["sway"], &[])
+      .generic_error("couldn't start sway")
+      .serve();
+
+    let (starter, client_manager) =
+      ClientManager::new(DEFAULT_RECONNECT_ATTEMPTS, unthrottled());
+    let manager = tokio::spawn(client_manager.run());
+
+    let (state_sender, state_receiver) = oneshot::channel();
+    let (_cancel_sender, cancel_receiver) = oneshot::channel();
+    starter
+      .send(("alice".into(), state_sender, cancel_receiver))
+      .unwrap();
+    let StatePacket::Error {
+      error: ClientError::GenericError(description),
+      retry_sender,
+    } = state_receiver.await.unwrap()
+    else {
+      panic!("expected an error");
+    };
+    assert_eq!(description, "session already active");
+
+    let (state_sender, state_receiver) = oneshot::channel();
+    let (_cancel_sender, cancel_receiver) = oneshot::channel();
+    retry_sender
+      .send(("alice".into(), state_sender, cancel_receiver))
+      .unwrap();
+    let StatePacket::Success { session_sender } = state_receiver.await.unwrap()
+    else {
+      panic!("expected success");
+    };
+
+    // greetd refusing the session is reported instead of ending the manager
+    let (state_sender, state_receiver) = oneshot::channel();
+    session_sender
+      .send((vec!["sway".into()], vec![], state_sender))
+      .unwrap();
+    let StatePacket::Error {
+      error: ClientError::GenericError(description),
+      ..
+    } = state_receiver.await.unwrap()
+    else {
+      panic!("expected an error");
+    };
+    assert_eq!(description, "couldn't start sway");
+
+    greetd.finish();
+    drop(manager);
+  }
+
+  async fn answer_password(
+    state_receiver: oneshot::Receiver<StatePacket>,
+    password: &str,
+  ) -> oneshot::Receiver<StatePacket> {
+    let StatePacket::Prompt {
+      response_sender, ..
+    } = state_receiver.await.unwrap()
+    else {
+      panic!("expected a prompt");
+    };
+    let (state_sender, state_receiver) = oneshot::channel();
+    response_sender
+      .send((Some(password.into()), state_sender))
+      .unwrap();
+    state_receiver
+  }
+
+  #[tokio::test]
+  async fn manager_reconnects_when_greetd_hangs_up() {
+    let greetd = Script::new()
+      .expect_session("alice")
+      .secret("Password:")
+      .disconnect()
+      .expect_session("alice")
+      .secret("Password:")
+      .expect_answer(Some("hunter2"))
+      .success()
+      .expect_start(&["sway"], &[])
+      .success()
+      .serve();
+
+    let (starter, client_manager) =
+      ClientManager::new(DEFAULT_RECONNECT_ATTEMPTS, unthrottled());
+    let manager = tokio::spawn(client_manager.run());
+
+    let (state_sender, state_receiver) = oneshot::channel();
+    let (_cancel_sender, cancel_receiver) = oneshot::channel();
+    starter
+      .send(("alice".into(), state_sender, cancel_receiver))
+      .unwrap();
+
+    // the answer goes nowhere, so the conversation starts over
+    let state_receiver = answer_password(state_receiver, "lost").await;
+    let StatePacket::Reconnecting { state_receiver } =
+      state_receiver.await.unwrap()
+    else {
+      panic!("expected a reconnection");
+    };
+
+    let state_receiver = answer_password(state_receiver, "hunter2").await;
+    let StatePacket::Success { session_sender } = state_receiver.await.unwrap()
+    else {
+      panic!("expected success");
+    };
+    let (state_sender, _state_receiver) = oneshot::channel();
+    session_sender
+      .send((vec!["sway".into()], vec![], state_sender))
+      .unwrap();
+
+    manager.await.unwrap();
+    greetd.finish();
+  }
+
+  #[tokio::test]
+  async fn manager_gives_up_reconnecting() {
+    let greetd = Script::new().expect_session("alice").disconnect().serve();
+
+    let (starter, client_manager) = ClientManager::new(2, unthrottled());
+    let manager = tokio::spawn(client_manager.run());
+
+    let (state_sender, mut state_receiver) = oneshot::channel();
+    let (_cancel_sender, cancel_receiver) = oneshot::channel();
+    starter
+      .send(("alice".into(), state_sender, cancel_receiver))
+      .unwrap();
+
+    // the fake stops listening once its script runs out
+    for _ in 0..2 {
+      let StatePacket::Reconnecting {
+        state_receiver: next,
+      } = state_receiver.await.unwrap()
+      else {
+        panic!("expected a reconnection");
+      };
+      state_receiver = next;
+    }
+    let StatePacket::Error {
+      error: ClientError::Disconnected { attempts: 2, .. },
+      ..
+    } = state_receiver.await.unwrap()
+    else {
+      panic!("expected to give up");
+    };
+
+    greetd.finish();
+    drop(manager);
+  }
+}
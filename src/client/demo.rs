@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use super::{AuthPrompt, CancelPacket, StatePacket, UsernamePacket, ask, fail};
+
+pub const DEFAULT_PASSWORD: &str = "demo";
+/// How long the pretend PAM takes to think about each answer.
+const THINKING_TIME: Duration = Duration::from_millis(800);
+
+/// Stands in for [`super::ClientManager`] without talking to greetd, so the
+/// UI can be tried out in an ordinary window. Any username is accepted along
+/// with `password`, and a successful login goes back to the start rather
+/// than launching anything.
+pub struct DemoManager {
+  receiver: oneshot::Receiver<UsernamePacket>,
+  password: String,
+}
+
+impl DemoManager {
+  pub fn new(password: String) -> (oneshot::Sender<UsernamePacket>, Self) {
+    let (sender, receiver) = oneshot::channel();
+    (sender, DemoManager { receiver, password })
+  }
+
+  pub async fn run(self) {
+    let Ok(mut packet) = self.receiver.await else {
+      return;
+    };
+
+    loop {
+      let (username, responder, mut cancel_receiver) = packet;
+
+      // as with the real client, a closed channel only ends things once a
+      // cancellation can't be coming through either
+      packet = tokio::select! {
+        biased;
+        Ok(CancelPacket(restart)) = &mut cancel_receiver => {
+          let Ok(next) = restart.await else { return };
+          next
+        }
+        Some(next) = Self::converse(&self.password, &username, responder) => {
+          next
+        }
+        else => return,
+      };
+    }
+  }
+
+  async fn converse(
+    password: &str,
+    username: &str,
+    responder: oneshot::Sender<StatePacket>,
+  ) -> Option<UsernamePacket> {
+    tokio::time::sleep(THINKING_TIME).await;
+    let (_, responder) = ask(
+      responder,
+      AuthPrompt::Info {
+        note: format!("demo mode, pretending to log in as {username}"),
+      },
+    )
+    .await?;
+
+    tokio::time::sleep(THINKING_TIME).await;
+    let (answer, responder) = ask(
+      responder,
+      AuthPrompt::Input {
+        prompt: String::from("Password:"),
+        secret: true,
+      },
+    )
+    .await?;
+    tokio::time::sleep(THINKING_TIME).await;
+
+    if answer.as_deref() != Some(password) {
+      let (_, responder) = ask(
+        responder,
+        AuthPrompt::Error {
+          note: String::from("that isn't the demo password"),
+        },
+      )
+      .await?;
+      return fail(responder, String::from("authentication failed")).await;
+    }
+
+    let (session_sender, session_receiver) = oneshot::channel();
+    responder
+      .send(StatePacket::Success { session_sender })
+      .ok()?;
+    let (command, _, responder) = session_receiver.await.ok()?;
+    tokio::time::sleep(THINKING_TIME).await;
+
+    fail(
+      responder,
+      format!("demo mode, not starting {}", command.join(" ")),
+    )
+    .await
+  }
+}
@@ -0,0 +1,240 @@
+use std::{
+  os::unix::net::{UnixListener, UnixStream},
+  path::PathBuf,
+  sync::{
+    Mutex, MutexGuard,
+    atomic::{AtomicUsize, Ordering},
+  },
+  thread::JoinHandle,
+};
+
+use greetd_ipc::{
+  AuthMessageType, ErrorType, Request, Response, codec::SyncCodec,
+};
+
+use super::GREETD_SOCK_ENV;
+
+// GREETD_SOCK is process-wide, so only one fake can be live at a time
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+enum Expected {
+  CreateSession { username: String },
+  Answer { response: Option<String> },
+  StartSession { cmd: Vec<String>, env: Vec<String> },
+  Cancel,
+}
+
+#[derive(Debug)]
+enum Step {
+  Expect(Expected),
+  Reply(Response),
+  Disconnect,
+}
+
+/// A scripted stand-in for greetd. Each request the greeter sends is checked
+/// against the next `expect_*` step, and every other step is written back in
+/// order.
+#[derive(Debug, Default)]
+pub struct Script {
+  steps: Vec<Step>,
+}
+
+impl Script {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn expect(mut self, expected: Expected) -> Self {
+    self.steps.push(Step::Expect(expected));
+    self
+  }
+
+  fn reply(mut self, response: Response) -> Self {
+    self.steps.push(Step::Reply(response));
+    self
+  }
+
+  fn auth_message(self, kind: AuthMessageType, message: &str) -> Self {
+    self.reply(Response::AuthMessage {
+      auth_message_type: kind,
+      auth_message: message.to_owned(),
+    })
+  }
+
+  pub fn expect_session(self, username: &str) -> Self {
+    self.expect(Expected::CreateSession {
+      username: username.to_owned(),
+    })
+  }
+
+  pub fn expect_answer(self, response: Option<&str>) -> Self {
+    self.expect(Expected::Answer {
+      response: response.map(str::to_owned),
+    })
+  }
+
+  pub fn expect_start(self, cmd: &[&str], env: &[&str]) -> Self {
+    self.expect(Expected::StartSession {
+      cmd: cmd.iter().map(|s| s.to_string()).collect(),
+      env: env.iter().map(|s| s.to_string()).collect(),
+    })
+  }
+
+  pub fn expect_cancel(self) -> Self {
+    self.expect(Expected::Cancel)
+  }
+
+  pub fn visible(self, prompt: &str) -> Self {
+    self.auth_message(AuthMessageType::Visible, prompt)
+  }
+
+  pub fn secret(self, prompt: &str) -> Self {
+    self.auth_message(AuthMessageType::Secret, prompt)
+  }
+
+  pub fn info(self, note: &str) -> Self {
+    self.auth_message(AuthMessageType::Info, note)
+  }
+
+  pub fn error(self, note: &str) -> Self {
+    self.auth_message(AuthMessageType::Error, note)
+  }
+
+  pub fn success(self) -> Self {
+    self.reply(Response::Success)
+  }
+
+  pub fn auth_error(self, description: &str) -> Self {
+    self.reply(Response::Error {
+      error_type: ErrorType::AuthError,
+      description: description.to_owned(),
+    })
+  }
+
+  /// Hangs up on the greeter. The steps after this one are served to
+  /// whoever connects next.
+  pub fn disconnect(mut self) -> Self {
+    self.steps.push(Step::Disconnect);
+    self
+  }
+
+  pub fn generic_error(self, description: &str) -> Self {
+    self.reply(Response::Error {
+      error_type: ErrorType::Error,
+      description: description.to_owned(),
+    })
+  }
+
+  /// Binds a fresh socket, points `GREETD_SOCK` at it and starts serving the
+  /// script on a background thread.
+  pub fn serve(self) -> FakeGreetd {
+    let env_guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let path = std::env::temp_dir().join(format!(
+      "cliffcrown-fake-greetd-{}-{}.sock",
+      std::process::id(),
+      SOCKET_COUNTER.fetch_add(1, Ordering::Relaxed),
+    ));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    // SAFETY: ENV_LOCK is held for as long as the variable is relied upon
+    unsafe { std::env::set_var(GREETD_SOCK_ENV, &path) };
+
+    let handle = std::thread::spawn(move || self.run(listener));
+
+    FakeGreetd {
+      path,
+      handle: Some(handle),
+      _env_guard: env_guard,
+    }
+  }
+
+  fn run(self, listener: UnixListener) -> Result<(), String> {
+    let mut connection = None::<UnixStream>;
+
+    for (index, step) in self.steps.into_iter().enumerate() {
+      if matches!(step, Step::Disconnect) {
+        connection = None;
+        continue;
+      }
+
+      let stream = match &mut connection {
+        Some(stream) => stream,
+        None => connection.insert(
+          listener
+            .accept()
+            .map_err(|e| format!("step {index}: accept failed: {e}"))?
+            .0,
+        ),
+      };
+
+      match step {
+        Step::Expect(expected) => {
+          let request = Request::read_from(stream)
+            .map_err(|e| format!("step {index}: read failed: {e}"))?;
+          if !expected.matches(&request) {
+            return Err(format!(
+              "step {index}: expected {expected:?}, got {request:?}"
+            ));
+          }
+        }
+        Step::Reply(response) => response
+          .write_to(stream)
+          .map_err(|e| format!("step {index}: write failed: {e}"))?,
+        Step::Disconnect => unreachable!(),
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Expected {
+  fn matches(&self, request: &Request) -> bool {
+    match (self, request) {
+      (
+        Self::CreateSession { username },
+        Request::CreateSession { username: actual },
+      ) => username == actual,
+      (
+        Self::Answer { response },
+        Request::PostAuthMessageResponse { response: actual },
+      ) => response == actual,
+      (
+        Self::StartSession { cmd, env },
+        Request::StartSession {
+          cmd: actual_cmd,
+          env: actual_env,
+        },
+      ) => cmd == actual_cmd && env == actual_env,
+      (Self::Cancel, Request::CancelSession) => true,
+      _ => false,
+    }
+  }
+}
+
+pub struct FakeGreetd {
+  path: PathBuf,
+  handle: Option<JoinHandle<Result<(), String>>>,
+  _env_guard: MutexGuard<'static, ()>,
+}
+
+impl FakeGreetd {
+  /// Waits for the script to run to completion, panicking with the first
+  /// mismatch if the greeter strayed from it.
+  pub fn finish(mut self) {
+    let handle = self.handle.take().unwrap();
+    if let Err(e) = handle.join().unwrap() {
+      panic!("fake greetd: {e}");
+    }
+  }
+}
+
+impl Drop for FakeGreetd {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
@@ -0,0 +1,275 @@
+use std::fs;
+
+use serde::Deserialize;
+use tokio::sync::oneshot;
+
+use super::{
+  AuthPrompt, CancelPacket, ClientError, StatePacket, UsernamePacket, ask, fail,
+};
+
+/// A PAM conversation written out ahead of time, read from a TOML file like
+///
+/// ```toml
+/// username = "alice"
+///
+/// [[prompt]]
+/// kind = "secret"
+/// message = "Password:"
+/// answer = "hunter2"
+///
+/// [outcome]
+/// result = "success"
+/// command = ["sway"]
+/// ```
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Conversation {
+  /// The username the greeter should ask for, if it matters.
+  username: Option<String>,
+  #[serde(default, rename = "prompt")]
+  prompts: Vec<ScriptedPrompt>,
+  outcome: Outcome,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+enum ScriptedPrompt {
+  Visible {
+    message: String,
+    answer: Option<String>,
+  },
+  Secret {
+    message: String,
+    answer: Option<String>,
+  },
+  Info {
+    message: String,
+  },
+  Error {
+    message: String,
+  },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "result", rename_all = "lowercase", deny_unknown_fields)]
+enum Outcome {
+  Success { command: Option<Vec<String>> },
+  Failure { reason: String },
+}
+
+impl ScriptedPrompt {
+  fn to_auth_prompt(&self) -> AuthPrompt {
+    match self.clone() {
+      Self::Visible { message, .. } => AuthPrompt::Input {
+        prompt: message,
+        secret: false,
+      },
+      Self::Secret { message, .. } => AuthPrompt::Input {
+        prompt: message,
+        secret: true,
+      },
+      Self::Info { message } => AuthPrompt::Info { note: message },
+      Self::Error { message } => AuthPrompt::Error { note: message },
+    }
+  }
+
+  fn expected_answer(&self) -> Option<&str> {
+    match self {
+      Self::Visible { answer, .. } | Self::Secret { answer, .. } => {
+        answer.as_deref()
+      }
+      Self::Info { .. } | Self::Error { .. } => None,
+    }
+  }
+}
+
+impl Conversation {
+  pub fn load(path: &str) -> Result<Self, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    toml::de::from_str(&contents).map_err(|e| e.to_string())
+  }
+}
+
+/// How far a run through the conversation got.
+enum Played {
+  Again(UsernamePacket),
+  Started,
+}
+
+/// Stands in for [`super::ClientManager`] by playing a [`Conversation`],
+/// keeping track of every way the greeter strayed from it. A failed login
+/// plays the conversation again for the next username.
+pub struct ScriptManager {
+  receiver: oneshot::Receiver<UsernamePacket>,
+  conversation: Conversation,
+  mismatches: Vec<String>,
+}
+
+impl ScriptManager {
+  pub fn new(
+    conversation: Conversation,
+  ) -> (oneshot::Sender<UsernamePacket>, Self) {
+    let (sender, receiver) = oneshot::channel();
+    (
+      sender,
+      ScriptManager {
+        receiver,
+        conversation,
+        mismatches: Vec::new(),
+      },
+    )
+  }
+
+  /// Plays the conversation until a session is started or the UI goes away,
+  /// then fails with a description of the mismatches if there were any.
+  pub async fn run(mut self) -> Result<(), String> {
+    let Ok(mut packet) = (&mut self.receiver).await else {
+      return Ok(());
+    };
+
+    loop {
+      let (username, responder, mut cancel_receiver) = packet;
+
+      packet = tokio::select! {
+        biased;
+        Ok(CancelPacket(restart)) = &mut cancel_receiver => {
+          let Ok(next) = restart.await else { break };
+          next
+        }
+        Some(played) = Self::play(
+          &self.conversation,
+          &mut self.mismatches,
+          &username,
+          responder,
+        ) => match played {
+          Played::Again(next) => next,
+          Played::Started => break,
+        },
+        else => break,
+      };
+    }
+
+    if self.mismatches.is_empty() {
+      Ok(())
+    } else {
+      Err(self.mismatches.join("\n"))
+    }
+  }
+
+  async fn play(
+    conversation: &Conversation,
+    mismatches: &mut Vec<String>,
+    username: &str,
+    mut responder: oneshot::Sender<StatePacket>,
+  ) -> Option<Played> {
+    if let Some(expected) = &conversation.username
+      && expected != username
+    {
+      mismatches.push(format!("expected user {expected:?}, got {username:?}"));
+      let reason = String::from("unknown user");
+      return fail(responder, reason).await.map(Played::Again);
+    }
+
+    // like PAM, a wrong answer only comes to light once the conversation
+    // is over
+    let mut answered_correctly = true;
+    for (index, prompt) in conversation.prompts.iter().enumerate() {
+      let (answer, next_responder) =
+        ask(responder, prompt.to_auth_prompt()).await?;
+      responder = next_responder;
+
+      if let Some(expected) = prompt.expected_answer()
+        && answer.as_deref() != Some(expected)
+      {
+        mismatches.push(format!(
+          "prompt {index}: expected {expected:?}, got {answer:?}"
+        ));
+        answered_correctly = false;
+      }
+    }
+
+    let command = match &conversation.outcome {
+      _ if !answered_correctly => {
+        let reason = String::from("authentication failed");
+        return fail(responder, reason).await.map(Played::Again);
+      }
+      Outcome::Failure { reason } => {
+        return fail(responder, reason.clone()).await.map(Played::Again);
+      }
+      Outcome::Success { command } => command,
+    };
+
+    let (session_sender, session_receiver) = oneshot::channel();
+    responder
+      .send(StatePacket::Success { session_sender })
+      .ok()?;
+    let (actual, _, responder) = session_receiver.await.ok()?;
+
+    if let Some(command) = command
+      && command != &actual
+    {
+      mismatches.push(format!("expected session {command:?}, got {actual:?}"));
+      let (retry_sender, retry_receiver) = oneshot::channel();
+      responder
+        .send(StatePacket::Error {
+          error: ClientError::GenericError(String::from(
+            "the script expected a different session",
+          )),
+          retry_sender,
+        })
+        .ok()?;
+      return retry_receiver.await.ok().map(Played::Again);
+    }
+
+    Some(Played::Started)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_a_conversation() {
+    let conversation: Conversation = toml::de::from_str(
+      r#"
+        username = "alice"
+
+        [[prompt]]
+        kind = "info"
+        message = "Touch your key"
+
+        [[prompt]]
+        kind = "secret"
+        message = "Password:"
+        answer = "hunter2"
+
+        [outcome]
+        result = "failure"
+        reason = "account expired"
+      "#,
+    )
+    .unwrap();
+
+    assert_eq!(conversation.username.as_deref(), Some("alice"));
+    assert_eq!(conversation.prompts.len(), 2);
+    assert_eq!(conversation.prompts[1].expected_answer(), Some("hunter2"));
+    assert!(matches!(
+      conversation.outcome,
+      Outcome::Failure { reason } if reason == "account expired"
+    ));
+  }
+
+  #[test]
+  fn rejects_unknown_keys() {
+    let result = toml::de::from_str::<Conversation>(
+      r#"
+        [outcome]
+        result = "success"
+        colour = "blue"
+      "#,
+    );
+
+    assert!(result.is_err());
+  }
+}
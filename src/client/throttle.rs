@@ -0,0 +1,186 @@
+use std::{
+  collections::BTreeMap,
+  time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::state_file;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottleConfig {
+  /// Seconds to wait after the first failure, doubling with each one after.
+  pub delay: u64,
+  pub max_delay: u64,
+  /// Failures in a row before locking the user out, or 0 to never do so.
+  pub lockout_after: u32,
+  pub lockout: u64,
+  /// Where to keep the counters between launches, if anywhere.
+  pub state_file: Option<String>,
+}
+
+impl Default for ThrottleConfig {
+  fn default() -> Self {
+    Self {
+      delay: 1,
+      max_delay: 30,
+      lockout_after: 5,
+      lockout: 300,
+      state_file: None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Failures {
+  count: u32,
+  /// Seconds since the epoch until which the user has to wait.
+  until: u64,
+}
+
+/// How long a user has to wait before they can try to log in again.
+#[derive(Debug, PartialEq)]
+pub struct Wait {
+  pub remaining: Duration,
+  pub locked_out: bool,
+}
+
+/// Counts failed logins for each username and works out how long to make
+/// the next attempt wait.
+pub struct Throttle {
+  config: ThrottleConfig,
+  failures: BTreeMap<String, Failures>,
+}
+
+fn seconds_since_epoch(time: SystemTime) -> u64 {
+  time
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs()
+}
+
+impl Throttle {
+  /// Starts from the counters in the state file if there is one.
+  pub fn load(config: ThrottleConfig) -> Self {
+    let failures = config
+      .state_file
+      .as_deref()
+      .map(state_file::load)
+      .unwrap_or_default();
+
+    Self { config, failures }
+  }
+
+  fn save(&self) {
+    let Some(path) = &self.config.state_file else {
+      return;
+    };
+
+    if let Err(e) = state_file::save(path, &self.failures) {
+      println!("couldn't save throttle state: {e}");
+    }
+  }
+
+  pub fn wait(&self, username: &str, now: SystemTime) -> Option<Wait> {
+    let failures = self.failures.get(username)?;
+    let remaining = (SystemTime::UNIX_EPOCH
+      + Duration::from_secs(failures.until))
+    .duration_since(now)
+    .ok()
+    .filter(|remaining| !remaining.is_zero())?;
+
+    Some(Wait {
+      remaining,
+      // the count starts over once someone has been locked out
+      locked_out: failures.count == 0,
+    })
+  }
+
+  pub fn record_failure(&mut self, username: &str, now: SystemTime) {
+    let ThrottleConfig {
+      delay,
+      max_delay,
+      lockout_after,
+      lockout,
+      ..
+    } = self.config;
+
+    let failures = self
+      .failures
+      .entry(username.to_owned())
+      .or_insert(Failures { count: 0, until: 0 });
+    failures.count += 1;
+
+    let wait = if lockout_after > 0 && failures.count >= lockout_after {
+      failures.count = 0;
+      lockout
+    } else {
+      let doublings = (failures.count - 1).min(u64::BITS - 1);
+      delay.saturating_mul(1 << doublings).min(max_delay)
+    };
+    failures.until = seconds_since_epoch(now) + wait;
+
+    self.save();
+  }
+
+  pub fn record_success(&mut self, username: &str) {
+    if self.failures.remove(username).is_some() {
+      self.save();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn at(seconds: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+  }
+
+  #[test]
+  fn delays_double_up_to_a_lockout() {
+    let mut throttle = Throttle::load(ThrottleConfig {
+      delay: 2,
+      max_delay: 5,
+      lockout_after: 4,
+      lockout: 60,
+      state_file: None,
+    });
+
+    let mut waits = Vec::new();
+    for _ in 0..4 {
+      throttle.record_failure("alice", at(1000));
+      waits.push(throttle.wait("alice", at(1000)).unwrap());
+    }
+    let seconds = waits
+      .iter()
+      .map(|w| w.remaining.as_secs())
+      .collect::<Vec<_>>();
+    assert_eq!(seconds, [2, 4, 5, 60]);
+    assert!(!waits[2].locked_out);
+    assert!(waits[3].locked_out);
+
+    assert_eq!(throttle.wait("bob", at(1000)), None);
+    assert_eq!(throttle.wait("alice", at(1060)), None);
+  }
+
+  #[test]
+  fn success_clears_the_count() {
+    let mut throttle = Throttle::load(ThrottleConfig::default());
+
+    throttle.record_failure("alice", at(0));
+    throttle.record_failure("alice", at(0));
+    throttle.record_success("alice");
+    throttle.record_failure("alice", at(0));
+
+    assert_eq!(
+      throttle.wait("alice", at(0)),
+      Some(Wait {
+        remaining: Duration::from_secs(1),
+        locked_out: false,
+      })
+    );
+  }
+}
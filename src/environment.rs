@@ -0,0 +1,112 @@
+use std::{fs::File, io::Read};
+
+/// Variables in the order they were given, later ones winning.
+pub type Variables = Vec<(String, String)>;
+
+/// Splits a `KEY=VALUE` assignment, as given to `--env`.
+pub fn parse_assignment(assignment: &str) -> Result<(String, String), String> {
+  let (key, value) = assignment
+    .split_once('=')
+    .ok_or_else(|| format!("expected KEY=VALUE, got {assignment:?}"))?;
+  let key = key.trim();
+
+  if key.is_empty()
+    || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+  {
+    return Err(format!("invalid variable name {key:?}"));
+  }
+
+  Ok((key.to_owned(), value.to_owned()))
+}
+
+/// Parses a file in the same format as `/etc/environment`: one `KEY=VALUE`
+/// per line, with blank lines and `#` comments ignored and values optionally
+/// wrapped in matching quotes. Like pam_env, lines that can't be understood
+/// are skipped; they come back separately as problems to report.
+pub fn parse_file(contents: &str) -> (Variables, Vec<String>) {
+  let mut variables = Vec::new();
+  let mut problems = Vec::new();
+  for (index, line) in contents.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    match parse_assignment(line) {
+      Ok((key, value)) => {
+        variables.push((key, unquote(value.trim()).to_owned()))
+      }
+      Err(e) => problems.push(format!("line {}: {e}", index + 1)),
+    }
+  }
+  (variables, problems)
+}
+
+pub fn read_file(path: &str) -> Result<(Variables, Vec<String>), String> {
+  let mut contents = String::new();
+  File::open(path)
+    .and_then(|mut file| file.read_to_string(&mut contents))
+    .map_err(|e| e.to_string())?;
+  Ok(parse_file(&contents))
+}
+
+fn unquote(value: &str) -> &str {
+  ['"', '\'']
+    .into_iter()
+    .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+    .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn assignments_need_a_plain_name() {
+    assert_eq!(
+      parse_assignment(" LANG=en_GB.UTF-8"),
+      Ok((String::from("LANG"), String::from("en_GB.UTF-8")))
+    );
+    // only the first `=` splits
+    assert_eq!(
+      parse_assignment("OPTS=a=b"),
+      Ok((String::from("OPTS"), String::from("a=b")))
+    );
+    assert_eq!(
+      parse_assignment("EMPTY="),
+      Ok((String::from("EMPTY"), String::new()))
+    );
+
+    for bad in ["NOVALUE", "=value", "BAD-NAME=1", "export X=1", "X.Y=1"] {
+      assert!(parse_assignment(bad).is_err(), "{bad:?}");
+    }
+  }
+
+  #[test]
+  fn bad_lines_are_skipped_and_reported() {
+    let (variables, problems) = parse_file(
+      "# a comment\n\
+       \n\
+       EDITOR=vim\n\
+       \x20 # an indented comment\n\
+       QUOTED=\"two words\"\n\
+       SINGLE='single'\n\
+       MISMATCHED=\"half'\n\
+       not an assignment\n\
+       PATH = /usr/bin \n",
+    );
+
+    let expected = [
+      ("EDITOR", "vim"),
+      ("QUOTED", "two words"),
+      ("SINGLE", "single"),
+      ("MISMATCHED", "\"half'"),
+      ("PATH", "/usr/bin"),
+    ];
+    assert_eq!(
+      variables,
+      expected.map(|(key, value)| (key.to_owned(), value.to_owned()))
+    );
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("line 8:"), "{}", problems[0]);
+  }
+}
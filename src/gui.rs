@@ -1,140 +1,374 @@
-use std::sync::Arc;
+use std::{
+  sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+  },
+  time::{Duration, Instant},
+};
 
 use egui::{
-  Align, Align2, Color32, Context, Event, EventFilter, FontId, Frame, Image,
-  Key, Layout, Modifiers, Pos2, Rect, RichText, Spinner, Stroke, TextStyle, Ui,
-  Vec2,
+  Align, Align2, Context, Event, EventFilter, Frame, Key, Label, Layout,
+  Modifiers, PointerButton, RichText, ScrollArea, Sense, Spinner, TextStyle,
+  Ui, Vec2,
 };
-use tokio::sync::{RwLock, oneshot};
+use tokio::sync::{RwLock, oneshot, watch};
 
 use crate::{
   Config,
-  client::{AuthPrompt, ClientManager, StatePacket, UsernamePacket},
+  background::Slideshow,
+  client::{AuthPrompt, CancelPacket, StatePacket, UsernamePacket},
+  power::PowerAction,
+  reload::{self, Reloaded},
+  theme::Theme,
+  users::User,
+  util::state_file,
 };
 
 mod hidden_input;
 mod util;
 
+/// How long before a prompt times out the bar starts counting down.
+const COUNTDOWN: Duration = Duration::from_secs(10);
+/// How long a note from PAM stays up before anything can replace it.
+const MIN_INFO_TIME: Duration = Duration::from_millis(1500);
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct GUI {
-  bg_uri: Option<String>,
+  slideshow: Slideshow,
   ui_state: Arc<UiState>,
   current_input: String,
+  session_names: Vec<String>,
+  users: Vec<User>,
+  allow_typed_username: bool,
+  show_transcript: bool,
+  theme: Theme,
+  power_actions: Vec<PowerAction>,
+  /// The power action waiting to be confirmed, if any.
+  pending_power: Option<usize>,
+  clear_input: watch::Receiver<()>,
+  reloads: watch::Receiver<Reloaded>,
+  /// Why the config file couldn't be reloaded, if it couldn't.
+  config_warning: Option<String>,
 }
 
 impl eframe::App for GUI {
   fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    if self.reloads.has_changed().unwrap_or(false) {
+      let reloaded = self.reloads.borrow_and_update().clone();
+      self.apply(ctx, reloaded);
+    }
+
     egui::CentralPanel::default()
-      .frame(Frame {
-        fill: Color32::BLACK,
-        ..Default::default()
-      })
+      // the slideshow paints the whole screen, starting with its fill
+      .frame(Frame::NONE)
       .show(ctx, |ui| {
-        if let Some(bg_uri) = self.bg_uri.as_ref() {
-          let image = Image::from_uri(bg_uri);
-
-          if let Some(size) =
-            image.load_and_calc_size(ui, ui.ctx().screen_rect().size())
-          {
-            let img_aspect = size.x / size.y;
-            let scr_aspect = ui.ctx().screen_rect().aspect_ratio();
-            let aspect = img_aspect / scr_aspect;
-
-            let adj_size = if aspect < 1.0 {
-              (1.0, aspect).into()
-            } else {
-              (1.0 / aspect, 1.0).into()
-            };
-            let adj_rect = Align2::CENTER_CENTER.align_size_within_rect(
-              adj_size,
-              Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
-            );
-
-            image.uv(adj_rect).paint_at(ui, ui.ctx().screen_rect());
-          }
-        }
-
+        self.slideshow.paint(ui);
         draw_ui(self, ui);
+        draw_config_warning(self, ui);
       });
   }
 }
 
+fn draw_config_warning(gui: &GUI, ui: &mut Ui) {
+  let Some(warning) = &gui.config_warning else {
+    return;
+  };
+
+  egui::Window::new("config warning")
+    .title_bar(false)
+    .resizable(false)
+    .movable(false)
+    .collapsible(false)
+    .interactable(false)
+    .pivot(Align2::CENTER_TOP)
+    .fixed_pos(ui.ctx().screen_rect().center_top() + Vec2::new(0.0, 20.0))
+    .show(ui.ctx(), |ui| {
+      ui.label(
+        RichText::new(warning)
+          .small()
+          .monospace()
+          .color(ui.visuals().warn_fg_color),
+      );
+    });
+}
+
 fn draw_ui(gui: &mut GUI, ui: &mut Ui) {
-  fn draw_bar<F: FnOnce(&mut Ui)>(ui: &mut Ui, contents: F) {
+  /// How the bar is drawn this frame, including what is in its corners.
+  struct Bar {
+    height: f32,
+    countdown: Option<u64>,
+    power_failure: Option<String>,
+  }
+
+  fn draw_bar<F: FnOnce(&mut Ui)>(
+    ui: &mut Ui,
+    interactable: bool,
+    bar: &Bar,
+    contents: F,
+  ) {
     egui::Window::new("bar")
       .title_bar(false)
-      .interactable(false)
+      .interactable(interactable)
       .resizable(false)
       .movable(false)
       .collapsible(false)
       .pivot(Align2::CENTER_CENTER)
       .fixed_pos(ui.ctx().screen_rect().center())
-      .fixed_size((ui.ctx().screen_rect().width(), 200.0))
-      .show(ui.ctx(), contents);
+      .fixed_size((ui.ctx().screen_rect().width(), bar.height))
+      .show(ui.ctx(), |ui| {
+        let rect = ui.max_rect();
+        contents(ui);
+        if let Some(seconds) = bar.countdown {
+          ui.painter().text(
+            rect.right_bottom() - Vec2::splat(5.0),
+            Align2::RIGHT_BOTTOM,
+            seconds.to_string(),
+            TextStyle::Small.resolve(ui.style()),
+            ui.visuals().weak_text_color(),
+          );
+        }
+        if let Some(failure) = &bar.power_failure {
+          ui.painter().text(
+            rect.left_bottom() + Vec2::new(5.0, -5.0),
+            Align2::LEFT_BOTTOM,
+            failure,
+            TextStyle::Small.resolve(ui.style()),
+            ui.visuals().error_fg_color,
+          );
+        }
+      });
+  }
+
+  fn draw_prompt(ui: &mut Ui, message: &str, input: &str) {
+    ui.columns_const(|columns: &mut [_; 2]| {
+      columns[0].allocate_ui_with_layout(
+        columns[0].available_size(),
+        Layout::right_to_left(Align::Center),
+        |ui| {
+          ui.add_space(25.0);
+          ui.label(RichText::new(message).strong());
+        },
+      );
+      columns[1].allocate_ui_with_layout(
+        columns[1].available_size(),
+        Layout::left_to_right(Align::Center),
+        |ui| {
+          ui.add_space(25.0);
+          ui.label(input);
+        },
+      );
+    })
+  }
+
+  fn draw_notice<F: FnOnce(&mut Ui)>(
+    ui: &mut Ui,
+    message: RichText,
+    footer: F,
+  ) {
+    let original_rect = ui.available_rect_before_wrap();
+    ui.centered_and_justified(|ui| ui.label(message));
+    ui.put(original_rect, |ui: &mut Ui| {
+      ui.allocate_ui_with_layout(
+        ui.available_size(),
+        Layout::bottom_up(Align::Center),
+        |ui| {
+          ui.add_space(5.0);
+          footer(ui);
+        },
+      )
+      .response
+    });
+  }
+
+  fn hint(text: &str) -> impl FnOnce(&mut Ui) {
+    move |ui| {
+      ui.label(RichText::new(text).small());
+    }
+  }
+
+  if gui.clear_input.has_changed().unwrap_or(false) {
+    gui.clear_input.mark_unchanged();
+    gui.current_input.clear();
+  }
+
+  if ui.input(|i| {
+    i.events.iter().any(|e| {
+      matches!(
+        e,
+        Event::Key { pressed: true, .. }
+          | Event::Text(_)
+          | Event::PointerButton { pressed: true, .. }
+      )
+    })
+  }) {
+    gui.ui_state.activity.send_replace(());
+    gui.ui_state.power_failure.send_replace(None);
   }
 
+  let deadline = *gui.ui_state.deadline.borrow();
+  let countdown = deadline.and_then(|deadline| {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining > COUNTDOWN {
+      ui.ctx().request_repaint_after(remaining - COUNTDOWN);
+      None
+    } else {
+      ui.ctx().request_repaint_after(Duration::from_millis(250));
+      Some(remaining.as_secs_f32().ceil() as u64)
+    }
+  });
+  let bar = Bar {
+    height: gui.theme.bar_height,
+    countdown,
+    power_failure: gui.ui_state.power_failure.borrow().clone(),
+  };
+
+  let mut submit_selection = false;
+  let mut typing_allowed = true;
+
   match &*tokio::task::block_in_place(|| gui.ui_state.display.blocking_read()) {
-    UiDisplayState::Empty => {}
+    UiDisplayState::Empty => {
+      if bar.power_failure.is_some() {
+        draw_bar(ui, false, &bar, |_| {});
+      }
+    }
     UiDisplayState::Message {
       message,
       show_input,
     } => {
-      draw_bar(ui, |ui| {
-        if let UiDisplayInputVisibility::NoInput {
-          show_confirm_message,
-        } = show_input
-        {
-          let original_rect = ui.available_rect_before_wrap();
-          ui.centered_and_justified(|ui| {
-            ui.label(RichText::new(message).strong())
-          });
-          if *show_confirm_message {
-            ui.put(original_rect, |ui: &mut Ui| {
-              ui.allocate_ui_with_layout(
-                ui.available_size(),
-                Layout::bottom_up(Align::Center),
-                |ui| {
-                  ui.add_space(5.0);
-                  ui.label(RichText::new("press <Enter> to continue").small());
-                },
-              )
-              .response
-            });
+      draw_bar(ui, false, &bar, |ui| {
+        let notice = RichText::new(message).strong();
+        match show_input {
+          UiDisplayInputVisibility::NoInput {
+            show_confirm_message: true,
+          } => draw_notice(ui, notice, hint("press <Enter> to continue")),
+          UiDisplayInputVisibility::NoInput {
+            show_confirm_message: false,
+          } => draw_notice(ui, notice, |_| {}),
+          UiDisplayInputVisibility::Waiting => draw_notice(ui, notice, |ui| {
+            ui.add(Spinner::new().color(gui.theme.spinner_colour.0));
+          }),
+          UiDisplayInputVisibility::Hidden => {
+            draw_prompt(ui, message, "<hidden>")
+          }
+          UiDisplayInputVisibility::Shown => {
+            draw_prompt(ui, message, &gui.current_input)
           }
-        } else {
-          ui.columns_const(|columns: &mut [_; 2]| {
-            columns[0].allocate_ui_with_layout(
-              columns[0].available_size(),
-              Layout::right_to_left(Align::Center),
-              |ui| {
-                ui.add_space(25.0);
-                ui.label(RichText::new(message).strong());
-              },
-            );
-            columns[1].allocate_ui_with_layout(
-              columns[1].available_size(),
-              Layout::left_to_right(Align::Center),
-              |ui| {
-                ui.add_space(25.0);
-                ui.label(
-                  if matches!(show_input, UiDisplayInputVisibility::Shown) {
-                    &gui.current_input
-                  } else {
-                    "<hidden>"
-                  },
-                );
-              },
-            );
-          })
         }
       });
+      draw_transcript(gui, ui);
+      draw_session_picker(gui, ui);
+    }
+    UiDisplayState::UserList => {
+      typing_allowed = gui.allow_typed_username;
+
+      if gui.current_input.is_empty() {
+        let user_count = gui.users.len();
+        let selected = gui.ui_state.selected_user.load(Ordering::Relaxed);
+        let previous = (selected + user_count - 1) % user_count;
+        let next = (selected + 1) % user_count;
+
+        let mut new_selection = ui.input(|i| {
+          if i.key_pressed(Key::ArrowUp) {
+            previous
+          } else if i.key_pressed(Key::ArrowDown) {
+            next
+          } else {
+            selected
+          }
+        });
+
+        draw_bar(ui, true, &bar, |ui| {
+          let rows_height = ui.text_style_height(&TextStyle::Small) * 2.0
+            + ui.text_style_height(&TextStyle::Body)
+            + ui.spacing().item_spacing.y * 2.0;
+          ui.add_space(((ui.available_height() - rows_height) / 2.0).max(0.0));
+
+          ui.vertical_centered(|ui| {
+            let row = |ui: &mut Ui, text: RichText| {
+              ui.add(Label::new(text).sense(Sense::click()))
+                .clicked_by(PointerButton::Primary)
+            };
+            let neighbour =
+              |user: &User| RichText::new(user.display_name()).small().weak();
+
+            // with two users the other one is shown below rather than twice
+            if user_count > 2 {
+              if row(ui, neighbour(&gui.users[previous])) {
+                new_selection = previous;
+              }
+            } else {
+              ui.label(RichText::new("").small());
+            }
+
+            if row(
+              ui,
+              RichText::new(gui.users[selected].display_name()).strong(),
+            ) {
+              submit_selection = true;
+            }
+
+            if user_count > 1 {
+              if row(ui, neighbour(&gui.users[next])) {
+                new_selection = next;
+              }
+            } else {
+              ui.label(RichText::new("").small());
+            }
+          });
+        });
+
+        gui
+          .ui_state
+          .selected_user
+          .store(new_selection, Ordering::Relaxed);
+      } else {
+        draw_bar(ui, false, &bar, |ui| {
+          draw_prompt(ui, "Username:", &gui.current_input)
+        });
+      }
+
+      draw_session_picker(gui, ui);
+    }
+    UiDisplayState::Countdown { message, until } => {
+      let remaining = until.saturating_duration_since(Instant::now());
+      ui.ctx().request_repaint_after(Duration::from_millis(250));
+      draw_bar(ui, false, &bar, |ui| {
+        draw_notice(
+          ui,
+          RichText::new(format!(
+            "{message}\ntry again in {}s",
+            remaining.as_secs_f32().ceil()
+          ))
+          .strong(),
+          |_| {},
+        );
+      });
+    }
+    UiDisplayState::Error { message } => {
+      draw_bar(ui, false, &bar, |ui| {
+        draw_notice(
+          ui,
+          RichText::new(message)
+            .strong()
+            .color(ui.visuals().error_fg_color),
+          hint("press <Enter> to retry"),
+        );
+      });
     }
-    UiDisplayState::Loading => draw_bar(ui, |ui| {
+    UiDisplayState::Loading => draw_bar(ui, false, &bar, |ui| {
       ui.centered_and_justified(|ui| {
-        ui.add(Spinner::new().size(50.0).color(Color32::GRAY))
+        ui.add(Spinner::new().size(50.0).color(gui.theme.spinner_colour.0))
       });
     }),
   }
 
+  draw_power_menu(gui, ui);
+
+  if ui.input(|i| i.key_pressed(Key::Escape)) {
+    gui.current_input.clear();
+    gui.ui_state.escape.send_replace(());
+  }
+
   match tokio::task::block_in_place(|| {
     gui.ui_state.input.blocking_read().get_type()
   }) {
@@ -149,11 +383,17 @@ fn draw_ui(gui: &mut GUI, ui: &mut Ui) {
           unreachable!()
         };
 
-        notifier.send(()).unwrap();
+        let _ = notifier.send(());
       }
     }
     UiInputStateType::Text => {
+      let mut submitted = submit_selection;
+
       for event in ui.input(|i| i.filtered_events(&EventFilter::default())) {
+        if submitted {
+          break;
+        }
+
         match event {
           Event::Key {
             key: Key::Enter,
@@ -161,16 +401,7 @@ fn draw_ui(gui: &mut GUI, ui: &mut Ui) {
             modifiers: Modifiers::NONE,
             ..
           } => {
-            let UiInputState::Text { responder } =
-              tokio::task::block_in_place(|| {
-                std::mem::take(&mut *gui.ui_state.input.blocking_write())
-              })
-            else {
-              unreachable!()
-            };
-            responder
-              .send(std::mem::take(&mut gui.current_input))
-              .unwrap();
+            submitted = true;
           }
           Event::Key {
             key: Key::Backspace,
@@ -180,13 +411,181 @@ fn draw_ui(gui: &mut GUI, ui: &mut Ui) {
           } => {
             gui.current_input.pop();
           }
-          Event::Text(text) => {
+          Event::Text(text) if typing_allowed => {
             gui.current_input.push_str(&text);
           }
           _ => {}
         }
       }
+
+      if submitted {
+        let UiInputState::Text { responder } =
+          tokio::task::block_in_place(|| {
+            std::mem::take(&mut *gui.ui_state.input.blocking_write())
+          })
+        else {
+          unreachable!()
+        };
+        // an empty answer at the user list stands for the selected user
+        let _ = responder.send(std::mem::take(&mut gui.current_input));
+      }
+    }
+  }
+}
+
+fn draw_power_menu(gui: &mut GUI, ui: &mut Ui) {
+  if gui.power_actions.is_empty() {
+    return;
+  }
+
+  // backing out of a confirmation shouldn't also abandon the login
+  let mut cancelled = gui.pending_power.is_some()
+    && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape));
+  let mut chosen = ui.input(|i| {
+    gui
+      .power_actions
+      .iter()
+      .position(|a| a.key.is_some_and(|key| i.key_pressed(key)))
+  });
+
+  egui::Window::new("power")
+    .title_bar(false)
+    .resizable(false)
+    .movable(false)
+    .collapsible(false)
+    .pivot(Align2::RIGHT_BOTTOM)
+    .fixed_pos(ui.ctx().screen_rect().right_bottom() - Vec2::splat(20.0))
+    .show(ui.ctx(), |ui| {
+      ui.horizontal(|ui| match gui.pending_power {
+        Some(index) => {
+          let label = gui.power_actions[index].label;
+          ui.label(RichText::new(format!("{label}?")).small().strong());
+          if ui.button(RichText::new("yes").small()).clicked() {
+            chosen = Some(index);
+          }
+          if ui.button(RichText::new("no").small()).clicked() {
+            cancelled = true;
+          }
+        }
+        None => {
+          for (index, action) in gui.power_actions.iter().enumerate() {
+            let text = match action.key {
+              Some(key) => format!("{} ({})", action.label, key.name()),
+              None => action.label.to_owned(),
+            };
+            if ui.button(RichText::new(text).small()).clicked() {
+              chosen = Some(index);
+            }
+          }
+        }
+      });
+    });
+
+  match (gui.pending_power, chosen) {
+    _ if cancelled => gui.pending_power = None,
+    (Some(pending), Some(index)) if pending == index => {
+      gui.pending_power = None;
+
+      let action = gui.power_actions[index].clone();
+      let state = gui.ui_state.clone();
+      let context = ui.ctx().clone();
+      state.power_failure.send_replace(None);
+      tokio::spawn(async move {
+        if let Err(e) = action.run().await {
+          state
+            .power_failure
+            .send_replace(Some(format!("couldn't {}: {e}", action.label)));
+          context.request_repaint();
+        }
+      });
     }
+    (_, Some(index)) => gui.pending_power = Some(index),
+    (_, None) => {}
+  }
+}
+
+/// From the middle of the screen to just clear of the bar.
+fn below_bar(gui: &GUI) -> Vec2 {
+  Vec2::new(0.0, gui.theme.bar_height / 2.0 + 20.0)
+}
+
+fn draw_transcript(gui: &GUI, ui: &mut Ui) {
+  if !gui.show_transcript {
+    return;
+  }
+
+  let transcript =
+    tokio::task::block_in_place(|| gui.ui_state.transcript.blocking_read());
+  if transcript.is_empty() {
+    return;
+  }
+
+  egui::Window::new("transcript")
+    .title_bar(false)
+    .resizable(false)
+    .movable(false)
+    .collapsible(false)
+    .pivot(Align2::CENTER_BOTTOM)
+    .fixed_pos(ui.ctx().screen_rect().center() - below_bar(gui))
+    .show(ui.ctx(), |ui| {
+      ScrollArea::vertical()
+        .max_height(gui.theme.transcript_height)
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+          for entry in transcript.iter() {
+            ui.label(match entry {
+              TranscriptEntry::Prompt(text) => RichText::new(text).strong(),
+              TranscriptEntry::Answer(text) => RichText::new(text),
+              TranscriptEntry::Info(text) => RichText::new(text).weak(),
+              TranscriptEntry::Error(text) => {
+                RichText::new(text).color(ui.visuals().error_fg_color)
+              }
+            });
+          }
+        });
+    });
+}
+
+fn draw_session_picker(gui: &GUI, ui: &mut Ui) {
+  let session_count = gui.session_names.len();
+  if session_count < 2 {
+    return;
+  }
+
+  let selected = gui.ui_state.selected_session.load(Ordering::Relaxed);
+  let backwards = session_count - 1;
+  let mut offset = ui.input(|i| {
+    i.key_pressed(Key::ArrowRight) as usize
+      + i.key_pressed(Key::ArrowLeft) as usize * backwards
+  });
+
+  egui::Window::new("session")
+    .title_bar(false)
+    .resizable(false)
+    .movable(false)
+    .collapsible(false)
+    .pivot(Align2::CENTER_TOP)
+    .fixed_pos(ui.ctx().screen_rect().center() + below_bar(gui))
+    .show(ui.ctx(), |ui| {
+      ui.horizontal(|ui| {
+        let arrow = |ui: &mut Ui, text| {
+          ui.add(Label::new(RichText::new(text).small()).sense(Sense::click()))
+            .clicked_by(PointerButton::Primary)
+        };
+
+        if arrow(ui, "◀") {
+          offset += backwards;
+        }
+        ui.label(RichText::new(&gui.session_names[selected]).small());
+        if arrow(ui, "▶") {
+          offset += 1;
+        }
+      });
+    });
+
+  let next = (selected + offset) % session_count;
+  if next != selected {
+    gui.ui_state.selected_session.store(next, Ordering::Relaxed);
   }
 }
 
@@ -194,46 +593,111 @@ impl GUI {
   pub fn new(cc: &eframe::CreationContext<'_>, config: Config) -> Self {
     egui_extras::install_image_loaders(&cc.egui_ctx);
 
-    cc.egui_ctx.style_mut(|s| {
-      s.visuals.window_shadow.offset = [0, 0];
-      s.visuals.window_shadow.spread = 10;
-      s.visuals.window_stroke = Stroke::new(5.0, Color32::DARK_GRAY);
+    let theme = config.appearance.theme.clone();
+    theme.apply(&cc.egui_ctx);
 
-      s.text_styles
-        .insert(TextStyle::Body, FontId::proportional(30.0));
-      s.text_styles
-        .insert(TextStyle::Small, FontId::proportional(16.0));
-    });
+    let slideshow =
+      Slideshow::new(config.appearance.background.clone(), cc.egui_ctx.clone());
+    let reloads = reload::watch_config(
+      config.config_path.clone(),
+      config.live_args.clone(),
+      config.appearance.clone(),
+      cc.egui_ctx.clone(),
+    );
 
-    let bg_uri = config
-      .bg_image
-      .as_ref()
-      .map(|path| format!("file://{path}"));
+    let session_names =
+      config.sessions.iter().map(|s| s.name.clone()).collect();
+    let users = config.users.clone();
+    let allow_typed_username = config.allow_typed_username;
+    let show_transcript = config.appearance.show_transcript;
+    let power_actions = config.power_actions.clone();
+    let config_warning = config.config_warning.clone();
+
+    // users picked from the list are preselected by UiManager instead
+    let current_input = config
+      .remembered
+      .last_user
+      .clone()
+      .filter(|name| {
+        config.restricted_user.is_none()
+          && allow_typed_username
+          && !users.iter().any(|u: &User| &u.name == name)
+      })
+      .unwrap_or_default();
 
-    let (starter, client_manager) = ClientManager::new().unwrap();
+    let starter = config.backend.start();
     let ui_manager = UiManager::new(cc.egui_ctx.clone(), config, starter);
     let state = ui_manager.state();
+    let clear_input = state.clear_input.subscribe();
 
-    tokio::spawn(client_manager.run());
     tokio::spawn(ui_manager.run());
 
     Self {
-      bg_uri,
+      slideshow,
       ui_state: state,
-      current_input: String::new(),
+      current_input,
+      session_names,
+      users,
+      allow_typed_username,
+      show_transcript,
+      theme,
+      power_actions,
+      pending_power: None,
+      clear_input,
+      reloads,
+      config_warning,
     }
   }
+
+  fn apply(&mut self, ctx: &Context, reloaded: Reloaded) {
+    let background = &reloaded.appearance.background;
+    if !self.slideshow.shows(background) {
+      self.slideshow = Slideshow::new(background.clone(), ctx.clone());
+    }
+
+    self.show_transcript = reloaded.appearance.show_transcript;
+    if reloaded.appearance.theme != self.theme {
+      reloaded.appearance.theme.apply(ctx);
+      self.theme = reloaded.appearance.theme;
+    }
+    self.config_warning = reloaded.warning;
+  }
 }
 
 #[derive(Default)]
 struct UiState {
   display: RwLock<UiDisplayState>,
   input: RwLock<UiInputState>,
+  selected_session: AtomicUsize,
+  selected_user: AtomicUsize,
+  escape: watch::Sender<()>,
+  activity: watch::Sender<()>,
+  /// Whether a prompt is waiting for the user to type or confirm, which is
+  /// the only time they can be timed out.
+  waiting_on_user: watch::Sender<bool>,
+  deadline: watch::Sender<Option<Instant>>,
+  clear_input: watch::Sender<()>,
+  /// What was said during the current attempt, apart from secret answers.
+  transcript: RwLock<Vec<TranscriptEntry>>,
+  /// Why the last power action failed, until the next key press.
+  power_failure: watch::Sender<Option<String>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum TranscriptEntry {
+  Prompt(String),
+  Answer(String),
+  Info(String),
+  Error(String),
 }
 
 #[derive(Clone, Copy)]
 enum UiDisplayInputVisibility {
-  NoInput { show_confirm_message: bool },
+  NoInput {
+    show_confirm_message: bool,
+  },
+  /// Waiting on greetd, after the message has been dealt with.
+  Waiting,
   Hidden,
   Shown,
 }
@@ -246,6 +710,14 @@ enum UiDisplayState {
     message: String,
     show_input: UiDisplayInputVisibility,
   },
+  UserList,
+  Countdown {
+    message: String,
+    until: Instant,
+  },
+  Error {
+    message: String,
+  },
   Loading,
 }
 
@@ -291,6 +763,14 @@ impl UiManager {
     username_sender: oneshot::Sender<UsernamePacket>,
   ) -> Self {
     let state = Arc::new(UiState::default());
+
+    if let Some(last_user) = &config.remembered.last_user
+      && let Some(index) =
+        config.users.iter().position(|u| &u.name == last_user)
+    {
+      state.selected_user.store(index, Ordering::Relaxed);
+    }
+
     Self {
       context,
       state,
@@ -307,46 +787,217 @@ impl UiManager {
     let UiManager {
       context,
       state,
-      start_client,
-      config,
+      mut start_client,
+      mut config,
     } = self;
 
-    let (notifier, notifiee) = oneshot::channel();
+    let mut escape = state.escape.subscribe();
+    let mut activity = state.activity.subscribe();
+    let prompt_timeout = config.prompt_timeout;
 
+    // the session picker starts on the session of whoever is most likely to
+    // log in, and follows the user once they have been chosen
+    let mut session_chosen_for = config
+      .restricted_user
+      .clone()
+      .or_else(|| config.remembered.last_user.clone());
+    if let Some(username) = &session_chosen_for
+      && let Some(index) =
+        config.remembered.session_for(username, &config.sessions)
     {
-      *state.input.write().await = UiInputState::Confirm { notifier };
+      state.selected_session.store(index, Ordering::Relaxed);
     }
 
-    notifiee.await.unwrap();
+    loop {
+      let (notifier, notifiee) = oneshot::channel();
+      {
+        *state.display.write().await = UiDisplayState::Empty;
+        *state.input.write().await = UiInputState::Confirm { notifier };
+        context.request_repaint();
+      }
 
-    let username = match config.restricted_user {
-      Some(username) => username,
-      None => {
-        let (username_sender, username_receiver) = oneshot::channel();
-        {
-          *state.display.write().await = UiDisplayState::Message {
-            message: String::from("Username:"),
-            show_input: UiDisplayInputVisibility::Shown,
-          };
-          *state.input.write().await = UiInputState::Text {
-            responder: username_sender,
-          };
-          context.request_repaint();
+      if notifiee.await.is_err() {
+        return;
+      }
+
+      loop {
+        let username = match &config.restricted_user {
+          Some(username) => username.clone(),
+          None => {
+            let (username_sender, username_receiver) = oneshot::channel();
+            {
+              *state.display.write().await = if config.users.is_empty() {
+                UiDisplayState::Message {
+                  message: String::from("Username:"),
+                  show_input: UiDisplayInputVisibility::Shown,
+                }
+              } else {
+                UiDisplayState::UserList
+              };
+              *state.input.write().await = UiInputState::Text {
+                responder: username_sender,
+              };
+              context.request_repaint();
+            }
+            let Ok(username) = username_receiver.await else {
+              return;
+            };
+            let username = match username {
+              username if username.is_empty() && !config.users.is_empty() => {
+                let selected = state.selected_user.load(Ordering::Relaxed);
+                config.users[selected].name.clone()
+              }
+              username => username,
+            };
+            {
+              *state.display.write().await = UiDisplayState::Loading;
+              context.request_repaint();
+            }
+            username
+          }
+        };
+
+        if session_chosen_for.as_ref() != Some(&username) {
+          if let Some(index) =
+            config.remembered.session_for(&username, &config.sessions)
+          {
+            state.selected_session.store(index, Ordering::Relaxed);
+          }
+          session_chosen_for = Some(username.clone());
         }
-        let username = username_receiver.await.unwrap();
-        {
-          *state.display.write().await = UiDisplayState::Loading;
-          context.request_repaint();
+
+        state.transcript.write().await.clear();
+        let (state_sender, state_receiver) = oneshot::channel();
+        let (cancel_sender, cancel_receiver) = oneshot::channel();
+        // if the client manager is gone, converse finds out straight away
+        let _ =
+          start_client.send((username.clone(), state_sender, cancel_receiver));
+
+        // escape presses from before the attempt started don't count
+        escape.mark_unchanged();
+        state.waiting_on_user.send_replace(false);
+
+        tokio::select! {
+          biased;
+          _ = escape.changed() => {
+            start_client = Self::abandon(&context, &state, cancel_sender).await;
+          }
+          retry_sender = Self::converse(
+            &context,
+            &state,
+            &mut config,
+            &username,
+            state_receiver,
+          ) => match retry_sender {
+            Some(retry_sender) => start_client = retry_sender,
+            None => return,
+          },
+          _ = Self::inactivity(&context, &state, &mut activity, prompt_timeout) => {
+            start_client = Self::abandon(&context, &state, cancel_sender).await;
+            state.deadline.send_replace(None);
+            state.clear_input.send_replace(());
+            break;
+          }
         }
-        username
+
+        state.deadline.send_replace(None);
       }
+    }
+  }
+
+  /// Cancels the current attempt at logging in, returning where to send the
+  /// next username once the client has been recovered.
+  async fn abandon(
+    context: &Context,
+    state: &UiState,
+    cancel_sender: oneshot::Sender<CancelPacket>,
+  ) -> oneshot::Sender<UsernamePacket> {
+    let (restart_sender, restart_receiver) = oneshot::channel();
+    let _ = cancel_sender.send(CancelPacket(restart_receiver));
+    {
+      *state.input.write().await = UiInputState::NoInput;
+      *state.display.write().await = UiDisplayState::Loading;
+      context.request_repaint();
+    }
+    restart_sender
+  }
+
+  /// Resolves once a prompt has waited on the user for `timeout` without a
+  /// key press or click, publishing the deadline so the bar can count down
+  /// to it. Without a timeout this never resolves.
+  async fn inactivity(
+    context: &Context,
+    state: &UiState,
+    activity: &mut watch::Receiver<()>,
+    timeout: Option<Duration>,
+  ) {
+    let Some(timeout) = timeout else {
+      return std::future::pending().await;
     };
 
-    let (state_sender, mut state_receiver) = oneshot::channel();
-    start_client.send((username, state_sender)).unwrap();
+    activity.mark_unchanged();
+    let mut waiting = state.waiting_on_user.subscribe();
+    loop {
+      // nothing counts down while PAM is busy, as with a fingerprint scan,
+      // or while a throttled attempt has its own countdown
+      if !*waiting.borrow_and_update() {
+        state.deadline.send_replace(None);
+        context.request_repaint();
+        let _ = waiting.changed().await;
+        continue;
+      }
+
+      let deadline = Instant::now() + timeout;
+      state.deadline.send_replace(Some(deadline));
+      context.request_repaint();
+
+      tokio::select! {
+        _ = tokio::time::sleep_until(deadline.into()) => return,
+        _ = activity.changed() => {}
+        _ = waiting.changed() => {}
+      }
+    }
+  }
+
+  /// Waits for the user to answer a prompt, with the inactivity timeout
+  /// running meanwhile.
+  async fn wait_on_user<T>(
+    state: &UiState,
+    answer: oneshot::Receiver<T>,
+  ) -> Option<T> {
+    state.waiting_on_user.send_replace(true);
+    let answer = answer.await.ok();
+    state.waiting_on_user.send_replace(false);
+    answer
+  }
+
+  /// Follows one attempt at logging in through to the end, returning where
+  /// to send the next username if it fails.
+  async fn converse(
+    context: &Context,
+    state: &UiState,
+    config: &mut Config,
+    username: &str,
+    mut state_receiver: oneshot::Receiver<StatePacket>,
+  ) -> Option<oneshot::Sender<UsernamePacket>> {
+    let mut starting = false;
+    let mut info_shown_at = None::<Instant>;
 
     loop {
-      match state_receiver.await.unwrap() {
+      let Ok(packet) = state_receiver.await else {
+        // once a session has been handed over the client only speaks up
+        // again if greetd refused to start it
+        if starting {
+          return None;
+        }
+        return Self::lost_client(context, state, config).await;
+      };
+
+      if let Some(shown_at) = info_shown_at.take() {
+        tokio::time::sleep_until((shown_at + MIN_INFO_TIME).into()).await;
+      }
+
+      match packet {
         StatePacket::Prompt {
           prompt,
           response_sender,
@@ -355,6 +1006,11 @@ impl UiManager {
           state_receiver = new_state_receiver;
           let response = match prompt {
             AuthPrompt::Input { prompt, secret } => {
+              state
+                .transcript
+                .write()
+                .await
+                .push(TranscriptEntry::Prompt(prompt.clone()));
               let (ui_responder, ui_respondee) = oneshot::channel();
               {
                 *state.display.write().await = UiDisplayState::Message {
@@ -371,22 +1027,43 @@ impl UiManager {
                 context.request_repaint();
               }
 
-              Some(ui_respondee.await.unwrap())
+              let answer = Self::wait_on_user(state, ui_respondee).await?;
+              if !secret {
+                state
+                  .transcript
+                  .write()
+                  .await
+                  .push(TranscriptEntry::Answer(answer.clone()));
+              }
+
+              Some(answer)
             }
             AuthPrompt::Info { note } => {
+              // steps like fingerprint scans only answer once they're done,
+              // so the note stays up with a spinner until something else
+              // comes along
+              state
+                .transcript
+                .write()
+                .await
+                .push(TranscriptEntry::Info(note.clone()));
               {
                 *state.display.write().await = UiDisplayState::Message {
                   message: note,
-                  show_input: UiDisplayInputVisibility::NoInput {
-                    show_confirm_message: false,
-                  },
+                  show_input: UiDisplayInputVisibility::Waiting,
                 };
                 context.request_repaint();
               }
+              info_shown_at = Some(Instant::now());
 
               None
             }
             AuthPrompt::Error { note } => {
+              state
+                .transcript
+                .write()
+                .await
+                .push(TranscriptEntry::Error(note.clone()));
               let (ui_notifier, ui_notifiee) = oneshot::channel();
               {
                 *state.display.write().await = UiDisplayState::Message {
@@ -401,23 +1078,336 @@ impl UiManager {
                 context.request_repaint();
               }
 
-              ui_notifiee.await.unwrap();
+              Self::wait_on_user(state, ui_notifiee).await?;
 
               None
             }
           };
-          response_sender.send((response, state_sender)).unwrap();
+          {
+            if let UiDisplayState::Message { show_input, .. } =
+              &mut *state.display.write().await
+            {
+              *show_input = UiDisplayInputVisibility::Waiting;
+            }
+            context.request_repaint();
+          }
+          let _ = response_sender.send((response, state_sender));
         }
-        StatePacket::Success { command_sender } => {
+        StatePacket::Success { session_sender } => {
           {
             *state.display.write().await = UiDisplayState::Loading;
             context.request_repaint();
           }
-          command_sender.send(config.command).unwrap();
+          let session =
+            &config.sessions[state.selected_session.load(Ordering::Relaxed)];
+
+          // failing to remember this login is never a reason to block it
+          config.remembered.record(username, &session.command);
+          if let Some(path) = &config.state_file
+            && let Err(e) = state_file::save(path, &config.remembered)
+          {
+            println!("couldn't save state file: {e}");
+          }
 
-          return;
+          let (state_sender, new_state_receiver) = oneshot::channel();
+          state_receiver = new_state_receiver;
+          starting = true;
+          let _ = session_sender.send((
+            session.command.clone(),
+            session.environment(&config.environment),
+            state_sender,
+          ));
+        }
+        StatePacket::Failure {
+          reason,
+          retry_sender,
+        } => {
+          state
+            .transcript
+            .write()
+            .await
+            .push(TranscriptEntry::Error(reason.clone()));
+          let (ui_notifier, ui_notifiee) = oneshot::channel();
+          {
+            *state.display.write().await = UiDisplayState::Message {
+              message: reason,
+              show_input: UiDisplayInputVisibility::NoInput {
+                show_confirm_message: true,
+              },
+            };
+            *state.input.write().await = UiInputState::Confirm {
+              notifier: ui_notifier,
+            };
+            context.request_repaint();
+          }
+
+          Self::wait_on_user(state, ui_notifiee).await?;
+
+          return Some(retry_sender);
+        }
+        StatePacket::Throttled {
+          until,
+          locked_out,
+          state_receiver: new_state_receiver,
+        } => {
+          state_receiver = new_state_receiver;
+          {
+            *state.input.write().await = UiInputState::NoInput;
+            *state.display.write().await = UiDisplayState::Countdown {
+              message: String::from(if locked_out {
+                "too many failed attempts, locked out"
+              } else {
+                "login failed"
+              }),
+              until,
+            };
+            context.request_repaint();
+          }
+        }
+        StatePacket::Reconnecting {
+          state_receiver: new_state_receiver,
+        } => {
+          state_receiver = new_state_receiver;
+          starting = false;
+          {
+            *state.input.write().await = UiInputState::NoInput;
+            *state.display.write().await = UiDisplayState::Message {
+              message: String::from("reconnecting…"),
+              show_input: UiDisplayInputVisibility::Waiting,
+            };
+            context.request_repaint();
+          }
+        }
+        StatePacket::Error {
+          error,
+          retry_sender,
+        } => {
+          Self::show_error(context, state, error.to_string()).await?;
+          return Some(retry_sender);
         }
       }
     }
   }
+
+  /// Shows `message` on the error screen until the user asks to retry,
+  /// returning `None` if the GUI went away first.
+  async fn show_error(
+    context: &Context,
+    state: &UiState,
+    message: String,
+  ) -> Option<()> {
+    let (notifier, notifiee) = oneshot::channel();
+    {
+      *state.display.write().await = UiDisplayState::Error { message };
+      *state.input.write().await = UiInputState::Confirm { notifier };
+      context.request_repaint();
+    }
+
+    Self::wait_on_user(state, notifiee).await
+  }
+
+  /// The client manager stopped answering, which only happens if it
+  /// panicked. Retrying starts a new one, with a new connection to greetd.
+  async fn lost_client(
+    context: &Context,
+    state: &UiState,
+    config: &Config,
+  ) -> Option<oneshot::Sender<UsernamePacket>> {
+    Self::show_error(
+      context,
+      state,
+      String::from("lost contact with the greetd client"),
+    )
+    .await?;
+
+    Some(config.backend.start())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    LiveArgs,
+    background::BackgroundConfig,
+    client::{
+      Backend,
+      script::{Conversation, ScriptManager},
+    },
+    reload::Appearance,
+    remember::Remembered,
+    sessions::Session,
+  };
+
+  const CONVERSATION: &str = r#"
+    username = "alice"
+
+    [[prompt]]
+    kind = "visible"
+    message = "Token:"
+    answer = "123456"
+
+    [[prompt]]
+    kind = "info"
+    message = "Touch your key"
+
+    [[prompt]]
+    kind = "secret"
+    message = "Password:"
+    answer = "hunter2"
+
+    [outcome]
+    result = "success"
+    command = ["sway"]
+  "#;
+
+  fn config(conversation: &Conversation) -> Config {
+    Config {
+      restricted_user: None,
+      users: Vec::new(),
+      allow_typed_username: true,
+      sessions: vec![Session::from_command(vec!["sway".into()])],
+      environment: Vec::new(),
+      state_file: None,
+      config_warning: None,
+      remembered: Remembered::default(),
+      prompt_timeout: None,
+      appearance: Appearance {
+        background: BackgroundConfig::default(),
+        show_transcript: false,
+        theme: Theme::default(),
+      },
+      config_path: String::from("/nonexistent/cliffcrown.toml"),
+      live_args: LiveArgs {
+        bg_image: None,
+        transcript: false,
+      },
+      power_actions: Vec::new(),
+      backend: Backend::Script {
+        conversation: conversation.clone(),
+      },
+    }
+  }
+
+  /// Plays the part of the person at the keyboard, confirming every message
+  /// and typing `typed` in order whenever asked for text. Once it runs out
+  /// it walks away, which ends the UI.
+  async fn act_as_user(state: Arc<UiState>, typed: &'static [&str]) {
+    let mut typed = typed.iter();
+    loop {
+      match std::mem::take(&mut *state.input.write().await) {
+        UiInputState::NoInput => {}
+        UiInputState::Confirm { notifier } => {
+          let _ = notifier.send(());
+        }
+        UiInputState::Text { responder } => match typed.next() {
+          Some(text) => {
+            let _ = responder.send(text.to_string());
+          }
+          None => return,
+        },
+      }
+      tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+  }
+
+  /// Runs a `UiManager` against `conversation` while typing `typed`, and
+  /// returns the script's verdict on how it went along with the UI state it
+  /// was left in.
+  async fn play(
+    conversation: &str,
+    typed: &'static [&str],
+  ) -> (Result<(), String>, Arc<UiState>) {
+    let conversation: Conversation = toml::de::from_str(conversation).unwrap();
+    let (starter, script) = ScriptManager::new(conversation.clone());
+    let script = tokio::spawn(script.run());
+
+    let ui_manager =
+      UiManager::new(Context::default(), config(&conversation), starter);
+    let state = ui_manager.state();
+    let user = tokio::spawn(act_as_user(state.clone(), typed));
+
+    let verdict = tokio::time::timeout(Duration::from_secs(15), async {
+      ui_manager.run().await;
+      user.abort();
+      script.await.unwrap()
+    })
+    .await
+    .expect("the login never finished");
+
+    (verdict, state)
+  }
+
+  #[tokio::test]
+  async fn answers_follow_the_script() {
+    play(CONVERSATION, &["alice", "123456", "hunter2"])
+      .await
+      .0
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn transcript_leaves_out_secrets() {
+    let (_, state) = play(CONVERSATION, &["alice", "123456", "hunter2"]).await;
+
+    assert_eq!(
+      *state.transcript.read().await,
+      [
+        TranscriptEntry::Prompt(String::from("Token:")),
+        TranscriptEntry::Answer(String::from("123456")),
+        TranscriptEntry::Info(String::from("Touch your key")),
+        TranscriptEntry::Prompt(String::from("Password:")),
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn wrong_answers_are_caught() {
+    let mismatches = play(CONVERSATION, &["alice", "123456", "hunter3"])
+      .await
+      .0
+      .unwrap_err();
+
+    assert_eq!(
+      mismatches,
+      r#"prompt 2: expected "hunter2", got Some("hunter3")"#
+    );
+  }
+
+  #[tokio::test]
+  async fn only_prompts_waiting_on_the_user_time_out() {
+    let context = Context::default();
+    let state = Arc::new(UiState::default());
+    let mut activity = state.activity.subscribe();
+    let timeout = Some(Duration::from_millis(50));
+
+    // PAM is busy, so the deadline never arms
+    let busy = tokio::time::timeout(
+      Duration::from_millis(200),
+      UiManager::inactivity(
+        &Context::default(),
+        &state,
+        &mut activity,
+        timeout,
+      ),
+    )
+    .await;
+    assert!(busy.is_err());
+    assert_eq!(*state.deadline.borrow(), None);
+
+    let (_answer, waiting) = oneshot::channel::<()>();
+    let waiting = UiManager::wait_on_user(&state, waiting);
+    tokio::select! {
+      _ = waiting => panic!("nobody answered"),
+      _ = UiManager::inactivity(&context, &state, &mut activity, timeout) => {}
+    }
+    assert!(state.deadline.borrow().is_some());
+  }
+
+  #[tokio::test]
+  async fn unexpected_users_are_caught() {
+    let mismatches = play(CONVERSATION, &["bob"]).await.0.unwrap_err();
+
+    assert_eq!(mismatches, r#"expected user "alice", got "bob""#);
+  }
 }
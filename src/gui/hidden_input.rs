@@ -1,13 +1,14 @@
-use std::f32::consts::{PI, TAU};
+use std::f32::consts::TAU;
 
 use egui::{
-  Color32, Event, EventFilter, Id, InnerResponse, Key, Response, Sense, Stroke,
-  Vec2, Widget, WidgetWithState,
+  Event, EventFilter, Id, Key, Response, Sense, Stroke, Vec2, Widget,
+  WidgetWithState,
 };
 use rand::Rng;
 
 use crate::gui::util::PainterExt;
 
+#[allow(dead_code)]
 pub struct Indicator<'a> {
   radius: f32,
   gap_width: f32,
@@ -22,6 +23,7 @@ pub struct Indicator<'a> {
 }
 
 #[derive(Clone, Default)]
+#[allow(dead_code)]
 pub enum IndicatorPhase {
   Visible(IndicatorInputState),
   Hidden(IndicatorInputState),
@@ -31,23 +33,27 @@ pub enum IndicatorPhase {
 }
 
 #[derive(Clone, Default)]
+#[allow(dead_code)]
 pub struct IndicatorInputState {
   highlighted_segment: Option<u8>,
   phase: IndicatorInputPhase,
 }
 
 #[derive(Clone, Copy, Default)]
+#[allow(dead_code)]
 pub enum IndicatorInputPhase {
   #[default]
   Add,
   Delete,
 }
 
+#[allow(dead_code)]
 pub struct IndicatorOutput {
   pub submitted: Option<String>,
   pub response: Response,
 }
 
+#[allow(dead_code)]
 impl<'a> Indicator<'a> {
   pub fn new(text: &'a mut String) -> Self {
     Self {
@@ -119,6 +125,7 @@ impl<'a> Indicator<'a> {
   }
 }
 
+#[allow(dead_code)]
 impl<'a> Indicator<'a> {
   pub fn show(self, ui: &mut egui::Ui) -> IndicatorOutput {
     let (id, rect) = ui.allocate_space(Vec2::splat(self.radius * 2.0));
@@ -249,6 +256,7 @@ impl<'a> WidgetWithState for Indicator<'a> {
   type State = IndicatorPhase;
 }
 
+#[allow(dead_code)]
 impl IndicatorPhase {
   pub fn set_input_phase(&mut self, input_phase: IndicatorInputPhase) {
     match self {
@@ -4,6 +4,7 @@ use egui::{
   Color32, Painter, Pos2, Shape, Stroke, Vec2, epaint::CubicBezierShape,
 };
 
+#[allow(dead_code)]
 pub trait PainterExt {
   fn draw_arc(
     &self,
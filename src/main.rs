@@ -1,71 +1,219 @@
 #![feature(sync_unsafe_cell)]
-use std::{fs::File, io::Read};
+use std::{
+  collections::BTreeMap,
+  fs::{self, File},
+  io::Read,
+  time::Duration,
+};
 
-use clap::Parser;
+use background::{
+  BackgroundConfig, BackgroundMode, BackgroundSource, Effects, Fill, Fit,
+  SlideshowConfig,
+};
+use clap::{Parser, Subcommand};
+use client::{Backend, script::Conversation, throttle::ThrottleConfig};
 use itertools::Itertools;
+use power::{PowerAction, PowerConfig};
+use reload::Appearance;
+use remember::Remembered;
 use serde::Deserialize;
+use sessions::Session;
+use theme::{Colour, Theme};
+use users::{User, UsersConfig};
 
+mod background;
+mod check;
 mod client;
+mod environment;
 mod gui;
+mod power;
+mod reload;
+mod remember;
+mod sessions;
+mod theme;
+mod users;
 mod util;
 
-const DEFAULT_CONFIG_PATH: &'static str = "/etc/greetd/cliffcrown.toml";
-const DEFAULT_COMMAND: [&'static str; 1] = ["bash"];
+const DEFAULT_CONFIG_PATH: &str = "/etc/greetd/cliffcrown.toml";
+const DEFAULT_COMMAND: [&str; 1] = ["bash"];
 
 #[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 struct StashedConfig {
   restricted_user: Option<String>,
   command: Option<Vec<String>>,
   #[serde(rename = "background")]
-  bg_image: Option<String>,
+  bg_image: Option<BackgroundSource>,
+  slideshow: Option<SlideshowConfig>,
+  background_mode: Option<BackgroundMode>,
+  letterbox_colour: Option<Colour>,
+  tile_scale: Option<f32>,
+  background_fill: Option<Fill>,
+  background_effects: Option<Effects>,
+  environment: Option<BTreeMap<String, String>>,
+  environment_file: Option<String>,
+  session_dirs: Option<Vec<String>>,
+  users: Option<UsersConfig>,
+  state_file: Option<String>,
+  prompt_timeout: Option<u64>,
+  transcript: Option<bool>,
+  reconnect_attempts: Option<u32>,
+  throttle: Option<ThrottleConfig>,
+  power: Option<PowerConfig>,
+  theme: Option<Theme>,
 }
 
+#[derive(Debug)]
 struct Config {
   restricted_user: Option<String>,
-  command: Vec<String>,
-  bg_image: Option<String>,
+  users: Vec<User>,
+  allow_typed_username: bool,
+  sessions: Vec<Session>,
+  environment: Vec<String>,
+  /// Where to remember logins, or `None` for pretend backends, which only
+  /// remember them until they exit.
+  state_file: Option<String>,
+  remembered: Remembered,
+  prompt_timeout: Option<Duration>,
+  appearance: Appearance,
+  config_path: String,
+  live_args: LiveArgs,
+  /// Why the config file couldn't be used at startup, to show on screen.
+  config_warning: Option<String>,
+  power_actions: Vec<PowerAction>,
+  backend: Backend,
 }
 
 #[derive(Parser, Debug)]
 struct CLIArgs {
   #[arg(short = 'u', long = "user")]
   restricted_user: Option<String>,
+  #[arg(short = 'l', long = "user-list")]
+  user_list: bool,
   #[arg(short = 'b', long = "bg")]
   bg_image: Option<String>,
+  #[arg(short = 'e', long = "env", value_parser = environment::parse_assignment)]
+  environment: Vec<(String, String)>,
+  #[arg(short = 'E', long = "env-file")]
+  environment_file: Option<String>,
+  #[arg(short = 's', long = "session-dir")]
+  session_dirs: Option<Vec<String>>,
+  #[arg(short = 'S', long = "state-file")]
+  state_file: Option<String>,
+  #[arg(short = 't', long = "prompt-timeout")]
+  prompt_timeout: Option<u64>,
+  #[arg(short = 'T', long = "transcript")]
+  transcript: bool,
+  #[arg(short = 'r', long = "reconnect-attempts")]
+  reconnect_attempts: Option<u32>,
+  #[arg(
+    long = "demo",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = client::demo::DEFAULT_PASSWORD
+  )]
+  demo_password: Option<String>,
+  #[arg(long = "script", conflicts_with = "demo_password")]
+  script: Option<String>,
   #[arg(short = 'C', long = "config", default_value = DEFAULT_CONFIG_PATH)]
   config_path: String,
   #[arg()]
   command: Option<Vec<String>>,
+  #[command(subcommand)]
+  action: Option<Action>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+  /// Check a config file for mistakes and print the configuration it gives
+  CheckConfig {
+    /// Defaults to the path given by --config
+    path: Option<String>,
+  },
+}
+
+/// The command line options that still win over a reloaded config file.
+#[derive(Debug, Clone)]
+struct LiveArgs {
+  bg_image: Option<String>,
+  transcript: bool,
+}
+
+impl LiveArgs {
+  fn merge(&self, stashed_config: &StashedConfig) -> Appearance {
+    let background = self
+      .bg_image
+      .clone()
+      .map(|arg| match Colour::try_from(arg.clone()) {
+        Ok(colour) => BackgroundSource::Fill(Fill::Colour(colour)),
+        Err(_) => BackgroundSource::Path(arg),
+      })
+      .or_else(|| stashed_config.bg_image.clone());
+    // a fill given as the background takes the place of background_fill
+    let (source, fill) = match background {
+      Some(BackgroundSource::Fill(fill)) => (None, fill),
+      source => (
+        source,
+        stashed_config.background_fill.clone().unwrap_or_default(),
+      ),
+    };
+
+    let default_fit = Fit::default();
+    Appearance {
+      background: BackgroundConfig {
+        source,
+        slideshow: stashed_config.slideshow.clone().unwrap_or_default(),
+        fit: Fit {
+          mode: stashed_config.background_mode.unwrap_or(default_fit.mode),
+          letterbox: stashed_config
+            .letterbox_colour
+            .unwrap_or(default_fit.letterbox),
+          tile_scale: stashed_config
+            .tile_scale
+            .unwrap_or(default_fit.tile_scale),
+        },
+        effects: stashed_config
+          .background_effects
+          .clone()
+          .unwrap_or_default(),
+        fill,
+      },
+      show_transcript: self.transcript
+        || stashed_config.transcript.unwrap_or_default(),
+      theme: stashed_config.theme.clone().unwrap_or_default(),
+    }
+  }
 }
 
 #[tokio::main]
 async fn main() {
-  let args = CLIArgs::parse();
-  let stashed_config: StashedConfig = File::open(args.config_path)
-    .inspect_err(|e| println!("couldn't open file: {e}"))
-    .ok()
-    .and_then(|mut cfile| {
-      let mut contents = String::new();
-      cfile
-        .read_to_string(&mut contents)
-        .inspect_err(|e| println!("couldn't read file: {e}"))
-        .ok()?;
-      toml::de::from_str(&contents)
-        .inspect_err(|e| println!("couldn't parse toml: {e}"))
-        .ok()
-    })
-    .inspect(|_: &StashedConfig| println!("config exists before default"))
-    .inspect(|c| println!("with background path {:?}", c.bg_image))
-    .unwrap_or_default();
+  let mut args = CLIArgs::parse();
+  if let Some(Action::CheckConfig { path }) = args.action.take() {
+    if let Some(path) = path {
+      args.config_path = path;
+    }
+    let ok = check::check_config(args);
+    std::process::exit(if ok { 0 } else { 1 });
+  }
 
-  let config = Config {
-    restricted_user: args.restricted_user.or(stashed_config.restricted_user),
-    bg_image: args.bg_image.or(stashed_config.bg_image),
-    command: args
-      .command
-      .or(stashed_config.command)
-      .unwrap_or_else(|| DEFAULT_COMMAND.into_iter().map_into().collect_vec()),
+  let (stashed_config, config_warning) = match read_config(&args.config_path) {
+    Ok((stashed_config, warnings)) => {
+      for warning in &warnings {
+        println!("{warning}");
+      }
+      (stashed_config, ignored_warning(&warnings))
+    }
+    Err(e) => {
+      println!("{e}");
+      // running without a config file at all is fine
+      let warning = fs::exists(&args.config_path).unwrap_or(true).then(|| {
+        format!("couldn't read config, so everything is at its defaults\n{e}")
+      });
+      (StashedConfig::default(), warning)
+    }
   };
+  let mut config = merge_config(args, stashed_config);
+  config.config_warning = config_warning;
 
   //eprintln!("{:?}", gui::GUI.run(config));
 
@@ -80,3 +228,293 @@ async fn main() {
   )
   .unwrap();
 }
+
+fn read_config_file(path: &str) -> Result<String, String> {
+  let mut contents = String::new();
+  File::open(path)
+    .map_err(|e| format!("couldn't open file: {e}"))?
+    .read_to_string(&mut contents)
+    .map_err(|e| format!("couldn't read file: {e}"))?;
+  Ok(contents)
+}
+
+/// Reads the config file for the greeter to run with. Keys that can't be
+/// used are left out and described in the warnings that come back, so that
+/// one stray or newer key never costs the rest of the file.
+fn read_config(path: &str) -> Result<(StashedConfig, Vec<String>), String> {
+  let table: toml::Table = toml::de::from_str(&read_config_file(path)?)
+    .map_err(|e| format!("couldn't parse toml: {e}"))?;
+
+  let mut warnings = Vec::new();
+  let mut usable = toml::Table::new();
+  for (key, mut value) in table {
+    loop {
+      let alone = toml::Table::from_iter([(key.clone(), value.clone())]);
+      let Err(e) = stashed_config_from(alone) else {
+        usable.insert(key, value);
+        break;
+      };
+      // an unknown key inside a table only costs that key
+      if let Some(name) = unknown_field(&e) {
+        if name == key {
+          warnings.push(format!("ignored unknown key {key}"));
+          break;
+        }
+        if let toml::Value::Table(section) = &mut value
+          && section.remove(name).is_some()
+        {
+          warnings.push(format!("ignored unknown key {key}.{name}"));
+          continue;
+        }
+      }
+      warnings.push(format!("ignored {key}: {e}"));
+      break;
+    }
+  }
+
+  // every key was fine on its own, so together they are too
+  let stashed_config = stashed_config_from(usable)?;
+  Ok((stashed_config, warnings))
+}
+
+/// What to show on screen about the keys [`read_config`] left out, if any.
+fn ignored_warning(warnings: &[String]) -> Option<String> {
+  (!warnings.is_empty())
+    .then(|| format!("left out part of the config\n{}", warnings.join("\n")))
+}
+
+/// Parses the config file the way `check-config` does, rejecting anything
+/// that [`read_config`] would leave out.
+fn read_config_strictly(path: &str) -> Result<StashedConfig, String> {
+  let stashed_config = toml::de::from_str(&read_config_file(path)?)
+    .map_err(|e| format!("couldn't parse toml: {e}"))?;
+  validate_config(&stashed_config)?;
+  Ok(stashed_config)
+}
+
+fn stashed_config_from(table: toml::Table) -> Result<StashedConfig, String> {
+  let stashed_config = toml::Value::Table(table)
+    .try_into()
+    .map_err(|e: toml::de::Error| e.message().to_owned())?;
+  validate_config(&stashed_config)?;
+  Ok(stashed_config)
+}
+
+/// The key named by serde's complaint about an unknown field, if that's what
+/// `error` is.
+fn unknown_field(error: &str) -> Option<&str> {
+  let (_, rest) = error.split_once("unknown field `")?;
+  rest.split_once('`').map(|(name, _)| name)
+}
+
+fn validate_config(stashed_config: &StashedConfig) -> Result<(), String> {
+  if let Some(theme) = &stashed_config.theme {
+    theme
+      .validate()
+      .map_err(|e| format!("invalid theme: {e}"))?;
+  }
+  if let Some(scale) = stashed_config.tile_scale
+    && !(scale.is_finite() && scale > 0.0)
+  {
+    return Err(format!("tile_scale has to be more than 0, not {scale}"));
+  }
+  if let Some(effects) = &stashed_config.background_effects {
+    effects
+      .validate()
+      .map_err(|e| format!("invalid background_effects: {e}"))?;
+  }
+  let fills = [
+    match &stashed_config.bg_image {
+      Some(BackgroundSource::Fill(fill)) => Some(fill),
+      _ => None,
+    },
+    stashed_config.background_fill.as_ref(),
+  ];
+  for fill in fills.into_iter().flatten() {
+    fill
+      .validate()
+      .map_err(|e| format!("invalid background: {e}"))?;
+  }
+  Ok(())
+}
+
+/// Combines the command line with the config file, the command line taking
+/// precedence.
+fn merge_config(args: CLIArgs, stashed_config: StashedConfig) -> Config {
+  let live_args = LiveArgs {
+    bg_image: args.bg_image,
+    transcript: args.transcript,
+  };
+  let appearance = live_args.merge(&stashed_config);
+
+  // later sources override earlier ones: env file, then config, then CLI
+  let mut environment = BTreeMap::new();
+  if let Some(path) = args.environment_file.or(stashed_config.environment_file)
+  {
+    match environment::read_file(&path) {
+      Ok((variables, problems)) => {
+        for problem in problems {
+          println!("skipped a line of {path}: {problem}");
+        }
+        environment.extend(variables);
+      }
+      Err(e) => println!("couldn't read environment file: {e}"),
+    }
+  }
+  environment.extend(stashed_config.environment.unwrap_or_default());
+  environment.extend(args.environment);
+
+  // an explicitly configured command comes first, so it stays the default
+  let session_dirs = args.session_dirs.or(stashed_config.session_dirs);
+  let mut sessions = args
+    .command
+    .or(stashed_config.command)
+    .map(Session::from_command)
+    .into_iter()
+    .collect_vec();
+  sessions.extend(sessions::discover(&session_dirs.unwrap_or_else(|| {
+    sessions::DEFAULT_SESSION_DIRS
+      .into_iter()
+      .map_into()
+      .collect_vec()
+  })));
+  if sessions.is_empty() {
+    sessions.push(Session::from_command(
+      DEFAULT_COMMAND.into_iter().map_into().collect_vec(),
+    ));
+  }
+
+  let mut users_config = stashed_config.users.unwrap_or_default();
+  users_config.list |= args.user_list;
+  let users = if users_config.list {
+    users::list(&users_config)
+      .inspect_err(|e| println!("couldn't read users: {e}"))
+      .unwrap_or_default()
+  } else {
+    Vec::new()
+  };
+
+  let backend = match (args.demo_password, args.script) {
+    (Some(password), _) => Backend::Demo { password },
+    (_, Some(path)) => Backend::Script {
+      // there is nothing useful to fall back to for a broken script
+      conversation: Conversation::load(&path).unwrap_or_else(|e| {
+        println!("couldn't load script {path}: {e}");
+        std::process::exit(1);
+      }),
+    },
+    (None, None) => Backend::Greetd {
+      reconnect_attempts: args
+        .reconnect_attempts
+        .or(stashed_config.reconnect_attempts)
+        .unwrap_or(client::DEFAULT_RECONNECT_ATTEMPTS),
+      throttle: stashed_config.throttle.unwrap_or_default(),
+    },
+  };
+
+  // pretend logins shouldn't read or overwrite the real greeter's state
+  let state_file = matches!(backend, Backend::Greetd { .. }).then(|| {
+    args
+      .state_file
+      .or(stashed_config.state_file)
+      .unwrap_or_else(|| remember::DEFAULT_STATE_PATH.into())
+  });
+  let remembered = state_file
+    .as_deref()
+    .map(util::state_file::load)
+    .unwrap_or_default();
+
+  Config {
+    restricted_user: args.restricted_user.or(stashed_config.restricted_user),
+    // there has to be some way to pick a user
+    allow_typed_username: users_config.allow_typing || users.is_empty(),
+    users,
+    sessions,
+    environment: environment
+      .into_iter()
+      .map(|(key, value)| format!("{key}={value}"))
+      .collect(),
+    state_file,
+    remembered,
+    // a timeout of zero means never giving up on the user
+    prompt_timeout: args
+      .prompt_timeout
+      .or(stashed_config.prompt_timeout)
+      .filter(|&seconds| seconds > 0)
+      .map(Duration::from_secs),
+    appearance,
+    config_path: args.config_path,
+    live_args,
+    config_warning: None,
+    power_actions: stashed_config
+      .power
+      .unwrap_or_default()
+      .actions(!matches!(backend, Backend::Greetd { .. })),
+    backend,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unusable_keys_cost_only_themselves() {
+    let path = std::env::temp_dir()
+      .join(format!("cliffcrown-lenient-{}.toml", std::process::id()));
+    let path = path.to_str().unwrap().to_owned();
+    fs::write(
+      &path,
+      r#"
+        restricted_user = "kiosk"
+        from_a_newer_version = true
+
+        [users]
+        list = true
+        max_iud = 2000
+
+        [theme]
+        bar_height = 0
+      "#,
+    )
+    .unwrap();
+
+    let (stashed_config, warnings) = read_config(&path).unwrap();
+    let strict = read_config_strictly(&path);
+    let _ = fs::remove_file(&path);
+
+    assert_eq!(stashed_config.restricted_user.as_deref(), Some("kiosk"));
+    assert!(stashed_config.users.is_some_and(|users| users.list));
+    assert!(stashed_config.theme.is_none());
+    assert_eq!(warnings.len(), 3, "{warnings:?}");
+    assert_eq!(warnings[0], "ignored unknown key from_a_newer_version");
+    assert!(
+      warnings[1].starts_with("ignored theme: "),
+      "{}",
+      warnings[1]
+    );
+    assert_eq!(warnings[2], "ignored unknown key users.max_iud");
+    assert!(strict.is_err());
+  }
+
+  #[test]
+  fn pretend_logins_leave_the_machine_alone() {
+    let merge = |args: &[&str]| {
+      let args = CLIArgs::parse_from(
+        ["cliffcrown", "--session-dir", "/nonexistent"]
+          .iter()
+          .chain(args),
+      );
+      merge_config(args, StashedConfig::default())
+    };
+
+    let demo = merge(&["--demo"]);
+    assert_eq!(demo.state_file, None);
+    assert!(!demo.power_actions.is_empty());
+    assert!(demo.power_actions.iter().all(|action| action.pretend));
+
+    let real = merge(&[]);
+    assert!(real.state_file.is_some());
+    assert!(real.power_actions.iter().all(|action| !action.pretend));
+  }
+}
@@ -0,0 +1,152 @@
+use egui::Key;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+  pub poweroff: Vec<String>,
+  pub reboot: Vec<String>,
+  pub suspend: Vec<String>,
+  /// Key names as egui knows them, like `F1`. Empty leaves an action unbound.
+  pub poweroff_key: String,
+  pub reboot_key: String,
+  pub suspend_key: String,
+}
+
+impl Default for PowerConfig {
+  fn default() -> Self {
+    let systemctl = |verb: &str| vec![String::from("systemctl"), verb.into()];
+    Self {
+      poweroff: systemctl("poweroff"),
+      reboot: systemctl("reboot"),
+      suspend: systemctl("suspend"),
+      poweroff_key: String::from("F1"),
+      reboot_key: String::from("F2"),
+      suspend_key: String::from("F3"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerAction {
+  pub label: &'static str,
+  pub command: Vec<String>,
+  pub key: Option<Key>,
+  /// Set for pretend logins, which shouldn't really power anything off.
+  pub pretend: bool,
+}
+
+impl PowerConfig {
+  /// The actions to offer, leaving out any with an empty command.
+  pub fn actions(self, pretend: bool) -> Vec<PowerAction> {
+    [
+      ("power off", self.poweroff, self.poweroff_key),
+      ("reboot", self.reboot, self.reboot_key),
+      ("suspend", self.suspend, self.suspend_key),
+    ]
+    .into_iter()
+    .filter(|(_, command, _)| !command.is_empty())
+    .map(|(label, command, key)| PowerAction {
+      label,
+      command,
+      key: match key.as_str() {
+        "" => None,
+        name => Key::from_name(name).or_else(|| {
+          println!("couldn't recognise key {name:?} to {label}");
+          None
+        }),
+      },
+      pretend,
+    })
+    .collect()
+  }
+}
+
+impl PowerAction {
+  /// Runs the command, failing if it can't be started or exits unhappily.
+  pub async fn run(&self) -> Result<(), String> {
+    if self.pretend {
+      return Err(format!("demo mode, not running {}", self.command.join(" ")));
+    }
+
+    let status = tokio::process::Command::new(&self.command[0])
+      .args(&self.command[1..])
+      .status()
+      .await
+      .map_err(|e| format!("{}: {e}", self.command[0]))?;
+
+    if status.success() {
+      Ok(())
+    } else {
+      Err(format!("{}: {status}", self.command[0]))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn empty_commands_and_keys_are_left_out() {
+    let actions = PowerConfig {
+      suspend: Vec::new(),
+      reboot_key: String::new(),
+      poweroff_key: String::from("NotAKey"),
+      ..PowerConfig::default()
+    }
+    .actions(false);
+
+    assert_eq!(
+      actions,
+      [
+        PowerAction {
+          label: "power off",
+          command: vec!["systemctl".into(), "poweroff".into()],
+          key: None,
+          pretend: false,
+        },
+        PowerAction {
+          label: "reboot",
+          command: vec!["systemctl".into(), "reboot".into()],
+          key: None,
+          pretend: false,
+        },
+      ]
+    );
+    assert_eq!(PowerConfig::default().actions(false)[2].key, Some(Key::F3));
+  }
+
+  #[tokio::test]
+  async fn failing_commands_are_reported() {
+    let action = |command: &[&str]| PowerAction {
+      label: "test",
+      command: command.iter().map(|s| s.to_string()).collect(),
+      key: None,
+      pretend: false,
+    };
+
+    assert_eq!(action(&["true"]).run().await, Ok(()));
+    assert_eq!(
+      action(&["false"]).run().await,
+      Err(String::from("false: exit status: 1"))
+    );
+    assert!(action(&["/nonexistent/poweroff"]).run().await.is_err());
+  }
+
+  #[tokio::test]
+  async fn pretend_actions_only_say_what_they_would_do() {
+    let marker = std::env::temp_dir()
+      .join(format!("cliffcrown-poweroff-{}", std::process::id()));
+    let action = PowerConfig {
+      poweroff: vec!["touch".into(), marker.to_str().unwrap().into()],
+      ..PowerConfig::default()
+    }
+    .actions(true)
+    .remove(0);
+
+    let e = action.run().await.unwrap_err();
+    assert!(e.starts_with("demo mode, not running touch "), "{e}");
+    assert!(!marker.exists());
+  }
+}
@@ -0,0 +1,140 @@
+use std::{
+  fs,
+  time::{Duration, SystemTime},
+};
+
+use egui::Context;
+use tokio::sync::watch;
+
+use crate::{
+  LiveArgs, background::BackgroundConfig, ignored_warning, read_config,
+  theme::Theme,
+};
+
+/// How often the config file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The settings that can change without restarting the greeter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Appearance {
+  pub background: BackgroundConfig,
+  pub show_transcript: bool,
+  pub theme: Theme,
+}
+
+#[derive(Debug, Clone)]
+pub struct Reloaded {
+  pub appearance: Appearance,
+  /// Why the latest edit couldn't be used, if it couldn't.
+  pub warning: Option<String>,
+}
+
+/// Enough to tell that a file changed, even with coarse timestamps.
+fn stamp(path: &str) -> Option<(SystemTime, u64)> {
+  let metadata = fs::metadata(path).ok()?;
+  Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Re-reads the config file at `path` whenever it changes, sending out the
+/// appearance it asks for. An edit that can't be used keeps the last good
+/// appearance and comes with a warning instead.
+pub fn watch_config(
+  path: String,
+  live_args: LiveArgs,
+  appearance: Appearance,
+  context: Context,
+) -> watch::Receiver<Reloaded> {
+  let (sender, receiver) = watch::channel(Reloaded {
+    appearance,
+    warning: None,
+  });
+
+  let mut last_stamp = stamp(&path);
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+      interval.tick().await;
+      let now_stamp = stamp(&path);
+      if now_stamp == last_stamp {
+        continue;
+      }
+      last_stamp = now_stamp;
+
+      let reloaded = match read_config(&path) {
+        Ok((stashed_config, warnings)) => Reloaded {
+          appearance: live_args.merge(&stashed_config),
+          warning: ignored_warning(&warnings),
+        },
+        Err(e) => Reloaded {
+          appearance: sender.borrow().appearance.clone(),
+          warning: Some(format!(
+            "couldn't reload config, keeping the previous one\n{e}"
+          )),
+        },
+      };
+      if sender.send(reloaded).is_err() {
+        return;
+      }
+      context.request_repaint();
+    }
+  });
+
+  receiver
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::background::BackgroundSource;
+
+  #[tokio::test]
+  async fn broken_edits_keep_the_last_good_appearance() {
+    let path = std::env::temp_dir()
+      .join(format!("cliffcrown-reload-{}.toml", std::process::id()));
+    let path = path.to_str().unwrap().to_owned();
+    fs::write(&path, "background = \"/a.png\"\n").unwrap();
+
+    let live_args = LiveArgs {
+      bg_image: None,
+      transcript: false,
+    };
+    let appearance = live_args.merge(&read_config(&path).unwrap().0);
+    let mut reloads =
+      watch_config(path.clone(), live_args, appearance, Context::default());
+
+    fs::write(&path, "background = \"/b.png\"\ntranscript = true\n").unwrap();
+    reloads.changed().await.unwrap();
+    let reloaded = reloads.borrow_and_update().clone();
+    assert_eq!(
+      reloaded.appearance,
+      Appearance {
+        background: BackgroundConfig {
+          source: Some(BackgroundSource::Path(String::from("/b.png"))),
+          ..BackgroundConfig::default()
+        },
+        show_transcript: true,
+        theme: Theme::default(),
+      }
+    );
+    assert_eq!(reloaded.warning, None);
+
+    fs::write(&path, "background = \"/c.png\ntranscript = false\n").unwrap();
+    reloads.changed().await.unwrap();
+    let reloaded = reloads.borrow_and_update().clone();
+    assert_eq!(
+      reloaded.appearance.background.source,
+      Some(BackgroundSource::Path(String::from("/b.png")))
+    );
+    assert!(reloaded.warning.unwrap().contains("couldn't parse toml"));
+
+    // a misspelled key is left out, but the rest still applies
+    fs::write(&path, "backgrund = \"/c.png\"\ntranscript = false\n").unwrap();
+    reloads.changed().await.unwrap();
+    let reloaded = reloads.borrow_and_update().clone();
+    assert_eq!(reloaded.appearance.background.source, None);
+    assert!(!reloaded.appearance.show_transcript);
+    assert!(reloaded.warning.unwrap().contains("backgrund"));
+
+    let _ = fs::remove_file(&path);
+  }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sessions::Session;
+
+pub const DEFAULT_STATE_PATH: &str = "/var/cache/cliffcrown/state.toml";
+
+/// What the greeter remembers between launches: the last user to log in
+/// successfully and the session command each user last started.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Remembered {
+  pub last_user: Option<String>,
+  sessions: BTreeMap<String, Vec<String>>,
+}
+
+impl Remembered {
+  pub fn record(&mut self, username: &str, command: &[String]) {
+    self.last_user = Some(username.to_owned());
+    self.sessions.insert(username.to_owned(), command.to_vec());
+  }
+
+  /// The index in `sessions` of the session `username` last started, if it
+  /// is still available.
+  pub fn session_for(
+    &self,
+    username: &str,
+    sessions: &[Session],
+  ) -> Option<usize> {
+    let command = self.sessions.get(username)?;
+    sessions.iter().position(|s| &s.command == command)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::Path};
+
+  use super::*;
+  use crate::util::state_file;
+
+  fn command(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|s| s.to_string()).collect()
+  }
+
+  #[test]
+  fn sessions_are_found_by_command() {
+    let sessions = [
+      Session::from_command(command(&["sway"])),
+      Session::from_command(command(&["niri", "--session"])),
+    ];
+    let mut remembered = Remembered::default();
+    remembered.record("alice", &command(&["sway"]));
+    remembered.record("bob", &command(&["niri", "--session"]));
+
+    assert_eq!(remembered.last_user.as_deref(), Some("bob"));
+    assert_eq!(remembered.session_for("alice", &sessions), Some(0));
+    assert_eq!(remembered.session_for("bob", &sessions), Some(1));
+    assert_eq!(remembered.session_for("carol", &sessions), None);
+
+    // a session that has since been uninstalled
+    remembered.record("alice", &command(&["startplasma-wayland"]));
+    assert_eq!(remembered.session_for("alice", &sessions), None);
+  }
+
+  #[test]
+  fn survives_a_round_trip() {
+    let path = std::env::temp_dir()
+      .join(format!("cliffcrown-remember-{}", std::process::id()))
+      .join("state.toml");
+    let path = path.to_str().unwrap().to_owned();
+
+    let mut remembered = Remembered::default();
+    remembered.record("alice", &command(&["sway", "-d"]));
+    state_file::save(&path, &remembered).unwrap();
+    let loaded: Remembered = state_file::load(&path);
+    assert_eq!(loaded.last_user.as_deref(), Some("alice"));
+    assert_eq!(loaded.sessions, remembered.sessions);
+
+    fs::write(&path, "last_user = [").unwrap();
+    let loaded: Remembered = state_file::load(&path);
+    let _ = fs::remove_dir_all(Path::new(&path).parent().unwrap());
+    assert_eq!(loaded.last_user, None);
+  }
+}
@@ -0,0 +1,314 @@
+use std::{collections::HashSet, fs, path::Path};
+
+pub const DEFAULT_SESSION_DIRS: [&str; 2] =
+  ["/usr/share/wayland-sessions", "/usr/share/xsessions"];
+
+const XDG_CURRENT_DESKTOP: &str = "XDG_CURRENT_DESKTOP";
+
+#[derive(Debug, Clone)]
+pub struct Session {
+  pub name: String,
+  pub command: Vec<String>,
+  pub desktop_names: Vec<String>,
+}
+
+impl Session {
+  pub fn from_command(command: Vec<String>) -> Self {
+    Self {
+      name: command.join(" "),
+      command,
+      desktop_names: Vec::new(),
+    }
+  }
+
+  /// The environment to start this session with. `XDG_CURRENT_DESKTOP` is
+  /// filled in from `DesktopNames` unless the configuration already set it.
+  pub fn environment(&self, base: &[String]) -> Vec<String> {
+    let mut environment = base.to_vec();
+    let already_set = base.iter().any(|v| {
+      v.split_once('=')
+        .is_some_and(|(k, _)| k == XDG_CURRENT_DESKTOP)
+    });
+
+    if !already_set && !self.desktop_names.is_empty() {
+      environment.push(format!(
+        "{XDG_CURRENT_DESKTOP}={}",
+        self.desktop_names.join(":")
+      ));
+    }
+
+    environment
+  }
+}
+
+/// Collects the sessions described by the `.desktop` files in `dirs`. As with
+/// other XDG data directories, a file in an earlier directory shadows one
+/// with the same name in a later directory.
+pub fn discover(dirs: &[String]) -> Vec<Session> {
+  let mut seen = HashSet::new();
+  let mut sessions = Vec::new();
+
+  for dir in dirs {
+    let Ok(entries) = fs::read_dir(dir) else {
+      continue;
+    };
+
+    let mut paths = entries
+      .filter_map(|entry| Some(entry.ok()?.path()))
+      .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+      .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+      let Some(file_name) = path.file_name().map(|n| n.to_owned()) else {
+        continue;
+      };
+      if !seen.insert(file_name) {
+        continue;
+      }
+
+      match read_desktop_entry(&path) {
+        Ok(Some(session)) => sessions.push(session),
+        Ok(None) => {}
+        Err(e) => println!("couldn't read session {}: {e}", path.display()),
+      }
+    }
+  }
+
+  sessions.sort_by(|a, b| a.name.cmp(&b.name));
+  sessions
+}
+
+fn read_desktop_entry(path: &Path) -> Result<Option<Session>, String> {
+  let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+  parse_desktop_entry(&contents)
+}
+
+/// Parses the `[Desktop Entry]` group of a session file, returning `None` for
+/// entries which are marked as hidden.
+fn parse_desktop_entry(contents: &str) -> Result<Option<Session>, String> {
+  let mut in_entry = false;
+  let mut name = None;
+  let mut exec = None;
+  let mut desktop_names = Vec::new();
+  let mut hidden = false;
+
+  for line in contents.lines().map(str::trim) {
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    if line.starts_with('[') {
+      in_entry = line == "[Desktop Entry]";
+      continue;
+    }
+
+    if !in_entry {
+      continue;
+    }
+
+    let Some((key, value)) = line.split_once('=') else {
+      continue;
+    };
+
+    match key.trim() {
+      "Name" => name = Some(value.trim().to_owned()),
+      "Exec" => exec = Some(value.trim().to_owned()),
+      "DesktopNames" => {
+        desktop_names = value
+          .split(';')
+          .map(str::trim)
+          .filter(|n| !n.is_empty())
+          .map(str::to_owned)
+          .collect();
+      }
+      "Hidden" | "NoDisplay" => hidden |= value.trim() == "true",
+      _ => {}
+    }
+  }
+
+  if hidden {
+    return Ok(None);
+  }
+
+  let exec = exec.ok_or("missing Exec key")?;
+  let command = split_exec(&exec)?;
+  if command.is_empty() {
+    return Err(String::from("empty Exec key"));
+  }
+
+  Ok(Some(Session {
+    name: name.unwrap_or_else(|| command.join(" ")),
+    command,
+    desktop_names,
+  }))
+}
+
+/// Splits an `Exec` value into arguments following the quoting rules of the
+/// desktop entry specification, dropping any field codes.
+fn split_exec(exec: &str) -> Result<Vec<String>, String> {
+  let mut arguments = Vec::new();
+  let mut current = None::<String>;
+  let mut chars = exec.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      ' ' | '\t' => {
+        arguments.extend(current.take());
+      }
+      '"' => {
+        let argument = current.get_or_insert_default();
+        loop {
+          match chars.next().ok_or("unterminated quote in Exec")? {
+            '"' => break,
+            '\\' => {
+              argument.push(chars.next().ok_or("unterminated quote in Exec")?)
+            }
+            c => argument.push(c),
+          }
+        }
+      }
+      '%' => match chars.next() {
+        Some('%') => current.get_or_insert_default().push('%'),
+        Some(_) => {
+          // field codes only make sense for launching with files or urls,
+          // which never happens for a session
+          current.get_or_insert_default();
+        }
+        None => return Err(String::from("dangling % in Exec")),
+      },
+      c => current.get_or_insert_default().push(c),
+    }
+  }
+  arguments.extend(current);
+
+  // a field code standing alone expands to nothing rather than an empty arg
+  Ok(arguments.into_iter().filter(|a| !a.is_empty()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+  }
+
+  #[test]
+  fn exec_follows_the_quoting_rules() {
+    assert_eq!(split_exec("sway").unwrap(), strings(&["sway"]));
+    assert_eq!(
+      split_exec("  env  FOO=1\tsway  ").unwrap(),
+      strings(&["env", "FOO=1", "sway"])
+    );
+    assert_eq!(
+      split_exec(r#"sh -c "exec \"$HOME/my session\" \\ \$x""#).unwrap(),
+      strings(&["sh", "-c", r#"exec "$HOME/my session" \ $x"#])
+    );
+    assert_eq!(
+      split_exec(r#"run pre"fix"post"#).unwrap(),
+      strings(&["run", "prefixpost"])
+    );
+
+    assert!(split_exec(r#"sh -c "oops"#).is_err());
+    assert!(split_exec(r#"sh "trailing\"#).is_err());
+  }
+
+  #[test]
+  fn field_codes_are_dropped() {
+    assert_eq!(
+      split_exec("startplasma %f --x=%u %U").unwrap(),
+      strings(&["startplasma", "--x="])
+    );
+    assert_eq!(
+      split_exec("echo 100%% %%done").unwrap(),
+      strings(&["echo", "100%", "%done"])
+    );
+    assert!(split_exec("echo 100%").is_err());
+  }
+
+  #[test]
+  fn entries_are_read_from_their_own_group() {
+    let session = parse_desktop_entry(
+      "# comment\n\
+       [Desktop Entry]\n\
+       Name=Sway\n\
+       Name[de]=Schwankung\n\
+       Comment=A tiling compositor\n\
+       Exec=sway --unsupported-gpu\n\
+       DesktopNames=sway;wlroots;\n\
+       \n\
+       [Desktop Action debug]\n\
+       Name=Debug\n\
+       Exec=sway -d\n",
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(session.name, "Sway");
+    assert_eq!(session.command, strings(&["sway", "--unsupported-gpu"]));
+    assert_eq!(session.desktop_names, strings(&["sway", "wlroots"]));
+  }
+
+  #[test]
+  fn unusable_entries_are_left_out() {
+    let parse = |body: &str| {
+      parse_desktop_entry(&format!("[Desktop Entry]\nName=Test\n{body}"))
+    };
+
+    assert!(parse("Exec=x\nHidden=true").unwrap().is_none());
+    assert!(parse("Exec=x\nNoDisplay=true").unwrap().is_none());
+    assert!(parse("Exec=x\nNoDisplay=false").unwrap().is_some());
+    assert!(parse("").is_err());
+    assert!(parse("Exec=%f").is_err());
+
+    // the name falls back to the command
+    let session = parse_desktop_entry("[Desktop Entry]\nExec=niri --session")
+      .unwrap()
+      .unwrap();
+    assert_eq!(session.name, "niri --session");
+  }
+
+  #[test]
+  fn earlier_directories_shadow_later_ones() {
+    let root = std::env::temp_dir()
+      .join(format!("cliffcrown-sessions-{}", std::process::id()));
+    let (first, second) = (root.join("first"), root.join("second"));
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    let entry = |name: &str, exec: &str| {
+      format!("[Desktop Entry]\nName={name}\nExec={exec}\n")
+    };
+    fs::write(first.join("sway.desktop"), entry("Sway", "sway")).unwrap();
+    fs::write(second.join("sway.desktop"), entry("Old Sway", "old")).unwrap();
+    fs::write(second.join("gnome.desktop"), entry("GNOME", "gnome")).unwrap();
+    fs::write(second.join("broken.desktop"), "[Desktop Entry]\n").unwrap();
+    fs::write(second.join("notes.txt"), entry("Notes", "notes")).unwrap();
+
+    let dirs = [&first, &second, &root.join("missing")]
+      .map(|dir| dir.to_string_lossy().into_owned());
+    let sessions = discover(&dirs);
+    let _ = fs::remove_dir_all(&root);
+
+    let found = sessions
+      .iter()
+      .map(|s| (s.name.as_str(), s.command[0].as_str()))
+      .collect::<Vec<_>>();
+    assert_eq!(found, [("GNOME", "gnome"), ("Sway", "sway")]);
+  }
+
+  #[test]
+  fn desktop_names_fill_in_the_current_desktop() {
+    let session = Session {
+      desktop_names: strings(&["sway", "wlroots"]),
+      ..Session::from_command(strings(&["sway"]))
+    };
+
+    assert_eq!(
+      session.environment(&strings(&["A=1"])),
+      strings(&["A=1", "XDG_CURRENT_DESKTOP=sway:wlroots"])
+    );
+    let set = strings(&["XDG_CURRENT_DESKTOP=GNOME"]);
+    assert_eq!(session.environment(&set), set);
+  }
+}
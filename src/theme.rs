@@ -0,0 +1,340 @@
+use std::{collections::BTreeMap, fs, sync::Arc};
+
+use ab_glyph::FontRef;
+use egui::{
+  Color32, Context, FontData, FontDefinitions, FontFamily, FontId, Stroke,
+  TextStyle,
+};
+use serde::Deserialize;
+
+/// A colour written as `#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb(r, g, b)` or
+/// `rgba(r, g, b, a)` with an alpha between 0 and 1.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Colour(pub Color32);
+
+impl TryFrom<String> for Colour {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<Self, String> {
+    parse_colour(&text)
+      .map(Colour)
+      .ok_or_else(|| format!("couldn't understand colour {text:?}"))
+  }
+}
+
+fn parse_colour(text: &str) -> Option<Color32> {
+  let text = text.trim();
+
+  if let Some(hex) = text.strip_prefix('#') {
+    let digits = hex
+      .chars()
+      .map(|c| c.to_digit(16).map(|d| d as u8))
+      .collect::<Option<Vec<_>>>()?;
+    return match digits[..] {
+      [r, g, b] => Some(Color32::from_rgb(r * 17, g * 17, b * 17)),
+      [r1, r2, g1, g2, b1, b2] => {
+        Some(Color32::from_rgb(r1 << 4 | r2, g1 << 4 | g2, b1 << 4 | b2))
+      }
+      [r1, r2, g1, g2, b1, b2, a1, a2] => {
+        Some(Color32::from_rgba_unmultiplied(
+          r1 << 4 | r2,
+          g1 << 4 | g2,
+          b1 << 4 | b2,
+          a1 << 4 | a2,
+        ))
+      }
+      _ => None,
+    };
+  }
+
+  let (name, arguments) = text.strip_suffix(')')?.split_once('(')?;
+  let arguments = arguments.split(',').map(str::trim).collect::<Vec<_>>();
+  let channel = |text: &str| text.parse::<u8>().ok();
+  match (name.trim(), &arguments[..]) {
+    ("rgb", [r, g, b]) => {
+      Some(Color32::from_rgb(channel(r)?, channel(g)?, channel(b)?))
+    }
+    ("rgba", [r, g, b, a]) => {
+      let alpha = a.parse::<f32>().ok().filter(|a| (0.0..=1.0).contains(a))?;
+      Some(Color32::from_rgba_unmultiplied(
+        channel(r)?,
+        channel(g)?,
+        channel(b)?,
+        (alpha * 255.0).round() as u8,
+      ))
+    }
+    _ => None,
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+  pub stroke_colour: Colour,
+  pub stroke_width: f32,
+  pub spinner_colour: Colour,
+  /// For config warnings.
+  pub warning_colour: Colour,
+  /// For errors from PAM, greetd, power actions and background images.
+  pub error_colour: Colour,
+  pub body_size: f32,
+  pub small_size: f32,
+  pub bar_height: f32,
+  /// How tall the transcript gets before it scrolls.
+  pub transcript_height: f32,
+  /// TTF or OTF files to load, by the name the lists below know them by.
+  pub fonts: BTreeMap<String, String>,
+  /// Fonts to try in order for each size, before egui's own.
+  pub body_fonts: Vec<String>,
+  pub small_fonts: Vec<String>,
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Self {
+      stroke_colour: Colour(Color32::DARK_GRAY),
+      stroke_width: 5.0,
+      spinner_colour: Colour(Color32::GRAY),
+      warning_colour: Colour(Color32::YELLOW),
+      error_colour: Colour(Color32::LIGHT_RED),
+      body_size: 30.0,
+      small_size: 16.0,
+      bar_height: 200.0,
+      transcript_height: 150.0,
+      fonts: BTreeMap::new(),
+      body_fonts: Vec::new(),
+      small_fonts: Vec::new(),
+    }
+  }
+}
+
+impl Theme {
+  /// Catches the sizes that would make a mess of the layout.
+  pub fn validate(&self) -> Result<(), String> {
+    let sizes = [
+      ("stroke_width", self.stroke_width, 0.0),
+      ("body_size", self.body_size, 1.0),
+      ("small_size", self.small_size, 1.0),
+      ("bar_height", self.bar_height, 1.0),
+      ("transcript_height", self.transcript_height, 1.0),
+    ];
+    for (name, size, minimum) in sizes {
+      if !(size.is_finite() && size >= minimum) {
+        return Err(format!("{name} has to be at least {minimum}, not {size}"));
+      }
+    }
+
+    let chains = [
+      ("body_fonts", &self.body_fonts),
+      ("small_fonts", &self.small_fonts),
+    ];
+    for (list, names) in chains {
+      if let Some(name) = names.iter().find(|n| !self.fonts.contains_key(*n)) {
+        return Err(format!("{list} has {name:?}, which isn't in fonts"));
+      }
+    }
+
+    Ok(())
+  }
+
+  /// egui's fonts with ours added. Every loaded font is also tried after
+  /// egui's for any text at all, so that names and PAM messages in other
+  /// scripts have something to fall back on.
+  fn font_definitions(&self) -> FontDefinitions {
+    let mut definitions = FontDefinitions::default();
+    for (name, path) in &self.fonts {
+      match load_font(path) {
+        Ok(data) => {
+          definitions.font_data.insert(name.clone(), Arc::new(data));
+          for family in [FontFamily::Proportional, FontFamily::Monospace] {
+            definitions
+              .families
+              .entry(family)
+              .or_default()
+              .push(name.clone());
+          }
+        }
+        Err(e) => println!("couldn't load font {path}: {e}"),
+      }
+    }
+
+    let fallback = definitions.families[&FontFamily::Proportional].clone();
+    for (family, names) in
+      [("body", &self.body_fonts), ("small", &self.small_fonts)]
+    {
+      let mut chain = names
+        .iter()
+        .filter(|name| definitions.font_data.contains_key(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+      for name in &fallback {
+        if !chain.contains(name) {
+          chain.push(name.clone());
+        }
+      }
+      // the families always exist, so a style never names a missing one
+      definitions
+        .families
+        .insert(FontFamily::Name(family.into()), chain);
+    }
+
+    definitions
+  }
+
+  pub fn apply(&self, context: &Context) {
+    context.set_fonts(self.font_definitions());
+    context.style_mut(|s| {
+      s.visuals.window_shadow.offset = [0, 0];
+      s.visuals.window_shadow.spread = 10;
+      s.visuals.window_stroke =
+        Stroke::new(self.stroke_width, self.stroke_colour.0);
+      s.visuals.warn_fg_color = self.warning_colour.0;
+      s.visuals.error_fg_color = self.error_colour.0;
+
+      s.text_styles.insert(
+        TextStyle::Body,
+        FontId::new(self.body_size, FontFamily::Name("body".into())),
+      );
+      s.text_styles.insert(
+        TextStyle::Small,
+        FontId::new(self.small_size, FontFamily::Name("small".into())),
+      );
+    });
+  }
+}
+
+pub fn load_font(path: &str) -> Result<FontData, String> {
+  let bytes = fs::read(path).map_err(|e| e.to_string())?;
+  // egui panics on fonts it can't parse, so they're weeded out here
+  FontRef::try_from_slice(&bytes).map_err(|e| e.to_string())?;
+  Ok(FontData::from_owned(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_colours() {
+    let colour = |text: &str| parse_colour(text);
+
+    assert_eq!(colour("#fff"), Some(Color32::WHITE));
+    assert_eq!(colour("#1a2B3c"), Some(Color32::from_rgb(0x1a, 0x2b, 0x3c)));
+    assert_eq!(
+      colour("#ff000080"),
+      Some(Color32::from_rgba_unmultiplied(255, 0, 0, 128))
+    );
+    assert_eq!(colour(" rgb(1, 2, 3) "), Some(Color32::from_rgb(1, 2, 3)));
+    assert_eq!(
+      colour("rgba(0,0,255,0.5)"),
+      Some(Color32::from_rgba_unmultiplied(0, 0, 255, 128))
+    );
+
+    for bad in [
+      "",
+      "#ff",
+      "#ggg",
+      "rgb(256, 0, 0)",
+      "rgba(0, 0, 0, 2)",
+      "red",
+    ] {
+      assert_eq!(colour(bad), None, "{bad:?}");
+    }
+  }
+
+  #[test]
+  fn rejects_bad_themes() {
+    let theme: Theme = toml::de::from_str(
+      r##"
+        stroke_colour = "#336699"
+        bar_height = 0
+      "##,
+    )
+    .unwrap();
+
+    assert_eq!(
+      theme.stroke_colour,
+      Colour(Color32::from_rgb(0x33, 0x66, 0x99))
+    );
+    assert_eq!(theme.body_size, 30.0);
+    assert!(theme.validate().is_err());
+    assert!(Theme::default().validate().is_ok());
+
+    let e = toml::de::from_str::<Theme>("spinner_colour = \"#12\"")
+      .unwrap_err()
+      .to_string();
+    assert!(e.contains("couldn't understand colour"), "{e}");
+
+    let theme = Theme {
+      body_fonts: vec![String::from("corporate")],
+      ..Theme::default()
+    };
+    assert!(theme.validate().is_err());
+  }
+
+  #[test]
+  fn overlay_colours_come_from_the_theme() {
+    let theme: Theme =
+      toml::de::from_str("error_colour = \"#f00\"\nwarning_colour = \"#ff0\"")
+        .unwrap();
+    let context = Context::default();
+    theme.apply(&context);
+
+    let visuals = context.style().visuals.clone();
+    assert_eq!(visuals.error_fg_color, Color32::RED);
+    assert_eq!(visuals.warn_fg_color, Color32::YELLOW);
+  }
+
+  #[test]
+  fn unloadable_fonts_fall_back_to_egui() {
+    let theme = Theme {
+      fonts: BTreeMap::from([
+        (
+          String::from("missing"),
+          String::from("/nonexistent/font.ttf"),
+        ),
+        (String::from("not a font"), String::from("/etc/passwd")),
+      ]),
+      body_fonts: vec![String::from("missing"), String::from("not a font")],
+      ..Theme::default()
+    };
+    assert!(theme.validate().is_ok());
+
+    let definitions = theme.font_definitions();
+    let builtin = FontDefinitions::default();
+    assert_eq!(
+      definitions.font_data.keys().collect::<Vec<_>>(),
+      builtin.font_data.keys().collect::<Vec<_>>()
+    );
+    assert_eq!(
+      definitions.families[&FontFamily::Name("body".into())],
+      builtin.families[&FontFamily::Proportional]
+    );
+  }
+
+  #[test]
+  fn loaded_fonts_come_first_in_their_chain() {
+    // one of egui's own fonts stands in for a real one
+    let path = std::env::temp_dir()
+      .join(format!("cliffcrown-font-{}.ttf", std::process::id()));
+    let builtin = FontDefinitions::default();
+    fs::write(&path, &builtin.font_data["Ubuntu-Light"].font).unwrap();
+
+    let theme = Theme {
+      fonts: BTreeMap::from([(
+        String::from("corporate"),
+        path.to_str().unwrap().to_owned(),
+      )]),
+      small_fonts: vec![String::from("corporate")],
+      ..Theme::default()
+    };
+    let definitions = theme.font_definitions();
+    let _ = fs::remove_file(&path);
+
+    let small = &definitions.families[&FontFamily::Name("small".into())];
+    let body = &definitions.families[&FontFamily::Name("body".into())];
+    assert_eq!(small.first().map(String::as_str), Some("corporate"));
+    assert_eq!(body.last().map(String::as_str), Some("corporate"));
+  }
+}
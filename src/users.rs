@@ -0,0 +1,139 @@
+use std::fs;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsersConfig {
+  pub list: bool,
+  pub allow_typing: bool,
+  pub passwd: String,
+  pub min_uid: u32,
+  pub max_uid: u32,
+  pub hidden_shells: Vec<String>,
+}
+
+impl Default for UsersConfig {
+  fn default() -> Self {
+    Self {
+      list: false,
+      allow_typing: true,
+      passwd: String::from("/etc/passwd"),
+      min_uid: 1000,
+      max_uid: 60000,
+      hidden_shells: [
+        "/bin/false",
+        "/sbin/nologin",
+        "/usr/bin/false",
+        "/usr/bin/nologin",
+        "/usr/sbin/nologin",
+      ]
+      .into_iter()
+      .map(String::from)
+      .collect(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+  pub name: String,
+  pub full_name: Option<String>,
+}
+
+impl User {
+  pub fn display_name(&self) -> &str {
+    self.full_name.as_deref().unwrap_or(&self.name)
+  }
+}
+
+/// Reads the accounts a person could log in as, leaving out system users by
+/// UID and anyone whose shell forbids logging in.
+pub fn list(config: &UsersConfig) -> Result<Vec<User>, String> {
+  let contents = fs::read_to_string(&config.passwd)
+    .map_err(|e| format!("{}: {e}", config.passwd))?;
+  Ok(parse_passwd(&contents, config))
+}
+
+fn parse_passwd(contents: &str, config: &UsersConfig) -> Vec<User> {
+  contents
+    .lines()
+    .filter_map(|line| {
+      let fields = line.split(':').collect::<Vec<_>>();
+      let [name, _, uid, _, gecos, _, shell] = fields[..] else {
+        return None;
+      };
+      let uid = uid.parse::<u32>().ok()?;
+
+      if !(config.min_uid..=config.max_uid).contains(&uid)
+        || config.hidden_shells.iter().any(|s| s == shell)
+      {
+        return None;
+      }
+
+      // the first comma separated GECOS field is the full name
+      let full_name = gecos
+        .split(',')
+        .next()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(str::to_owned);
+
+      Some(User {
+        name: name.to_owned(),
+        full_name,
+      })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PASSWD: &str = "\
+root:x:0:0:root:/root:/bin/bash
+nobody:x:65534:65534:Nobody:/:/usr/bin/nologin
+alice:x:1000:1000:Alice Liddell,,,:/home/alice:/bin/zsh
+bob:x:1001:1001::/home/bob:/bin/bash
+svc:x:1002:1002:Service:/var/lib/svc:/usr/sbin/nologin
+broken line
+";
+
+  #[test]
+  fn filters_system_and_nologin_accounts() {
+    let users = parse_passwd(PASSWD, &UsersConfig::default());
+
+    assert_eq!(
+      users,
+      [
+        User {
+          name: "alice".into(),
+          full_name: Some("Alice Liddell".into()),
+        },
+        User {
+          name: "bob".into(),
+          full_name: None,
+        },
+      ]
+    );
+    assert_eq!(users[0].display_name(), "Alice Liddell");
+    assert_eq!(users[1].display_name(), "bob");
+  }
+
+  #[test]
+  fn honours_uid_range_and_shells() {
+    let config = UsersConfig {
+      min_uid: 0,
+      max_uid: 1000,
+      hidden_shells: vec![String::from("/bin/zsh")],
+      ..Default::default()
+    };
+
+    let names = parse_passwd(PASSWD, &config)
+      .into_iter()
+      .map(|u| u.name)
+      .collect::<Vec<_>>();
+    assert_eq!(names, ["root"]);
+  }
+}
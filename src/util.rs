@@ -1,2 +1,3 @@
 pub mod change_detector;
 pub mod late_init;
+pub mod state_file;
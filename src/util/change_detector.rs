@@ -1,10 +1,12 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
+#[allow(dead_code)]
 pub struct ChangeDetector<T> {
   target: T,
   changed: AtomicBool,
 }
 
+#[allow(dead_code)]
 impl<T> ChangeDetector<T> {
   pub fn new(target: T) -> Self {
     ChangeDetector {
@@ -27,7 +29,7 @@ impl<T> ChangeDetector<T> {
   }
 
   pub fn get_if_changed(&self) -> Option<&T> {
-    self.take_change().then_some(&*self)
+    self.take_change().then_some(self)
   }
 }
 
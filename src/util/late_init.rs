@@ -7,11 +7,13 @@ use std::{
   },
 };
 
+#[allow(dead_code)]
 pub struct LateInitialiser<T> {
   initialised: Arc<AtomicBool>,
   value_ref: Arc<SyncUnsafeCell<MaybeUninit<T>>>,
 }
 
+#[allow(dead_code)]
 impl<T> LateInitialiser<T> {
   pub fn initialise(self, value: T) {
     unsafe { &mut *self.value_ref.get() }.write(value);
@@ -19,11 +21,13 @@ impl<T> LateInitialiser<T> {
   }
 }
 
+#[allow(dead_code)]
 pub struct LateInitialisee<T> {
   initialised: Arc<AtomicBool>,
   value_ref: Arc<SyncUnsafeCell<MaybeUninit<T>>>,
 }
 
+#[allow(dead_code)]
 impl<T> LateInitialisee<T> {
   pub fn get(&self) -> Option<&T> {
     if !self.initialised.load(Ordering::Acquire) {
@@ -52,6 +56,7 @@ impl<T> std::ops::Drop for LateInitialisee<T> {
   }
 }
 
+#[allow(dead_code)]
 pub fn late_initialise<T>() -> (LateInitialiser<T>, LateInitialisee<T>) {
   let initialised = Arc::new(AtomicBool::new(false));
   let value_ref = Arc::new(SyncUnsafeCell::new(MaybeUninit::uninit()));
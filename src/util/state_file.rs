@@ -0,0 +1,25 @@
+use std::{fs, path::Path};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Reads state kept between launches, treating anything unreadable as a
+/// fresh start.
+pub fn load<T: DeserializeOwned + Default>(path: &str) -> T {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|contents| {
+      toml::de::from_str(&contents)
+        .inspect_err(|e| println!("couldn't parse state file {path}: {e}"))
+        .ok()
+    })
+    .unwrap_or_default()
+}
+
+/// Writes `state` to `path`, creating the directory it goes in if needed.
+pub fn save<T: Serialize>(path: &str, state: &T) -> Result<(), String> {
+  let contents = toml::to_string(state).map_err(|e| e.to_string())?;
+  if let Some(parent) = Path::new(path).parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  fs::write(path, contents).map_err(|e| e.to_string())
+}